@@ -0,0 +1,41 @@
+use crate::prelude::*;
+
+// mt_septune [seed] [--turn-based] [--diagonal] [--headless <script>]
+pub struct CommandLine {
+    pub seed: Seed,
+    pub mode: GameMode,
+    pub rules: Rules,
+    pub headless_script: Option<String>,
+}
+
+impl CommandLine {
+    pub fn parse() -> Self {
+        let mut seed = None;
+        let mut mode = GameMode::RealTime;
+        let mut rules = Rules::default();
+        let mut headless_script = None;
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--turn-based" {
+                mode = GameMode::TurnBased;
+            } else if arg == "--diagonal" {
+                rules.diagonals = true;
+            } else if arg == "--headless" {
+                headless_script = Some(args.next().unwrap_or_default());
+            } else {
+                seed = Seed::parse(&arg);
+                if seed.is_none() {
+                    eprintln!("'{arg}' is not a valid seed, picking a random one");
+                }
+            }
+        }
+
+        Self {
+            seed: seed.unwrap_or_else(Seed::random),
+            mode,
+            rules,
+            headless_script,
+        }
+    }
+}
@@ -1,61 +1,338 @@
 pub use crate::prelude::*;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Render {
     pub color: ColorPair,
     pub glyph: FontCharType,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Player;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Enemy;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Item;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+// `target` is the tile the item was aimed at, if it needed aiming
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ActivateItem {
     pub used_by: Entity,
     pub item: Entity,
+    pub target: Option<Point>,
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Carried(pub Entity);
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Weapon,
+    Armor,
+    Trinket,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Equippable {
+    pub slot: EquipmentSlot,
+}
+
+// Worn or wielded by the entity, as opposed to just Carried
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Equipped(pub Entity);
+
+// What a piece of equipment adds to its wearer's Attack and Defense
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatBonus {
+    pub damage: i32,
+    pub accuracy: i32,
+    pub crit_chance: i32,
+    pub armor: i32,
+    pub evasion: i32,
+}
+
+impl StatBonus {
+    // e.g. "damage +1, evasion -5", leaving out whatever isn't changed
+    pub fn summary(&self) -> String {
+        [
+            ("damage", self.damage),
+            ("accuracy", self.accuracy),
+            ("crit chance", self.crit_chance),
+            ("armor", self.armor),
+            ("evasion", self.evasion),
+        ]
+        .iter()
+        .filter(|(_, amount)| *amount != 0)
+        .map(|(stat, amount)| format!("{stat} {amount:+}"))
+        .collect::<Vec<String>>()
+        .join(", ")
+    }
+}
+
+impl std::ops::Add for StatBonus {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            damage: self.damage + other.damage,
+            accuracy: self.accuracy + other.accuracy,
+            crit_chance: self.crit_chance + other.crit_chance,
+            armor: self.armor + other.armor,
+            evasion: self.evasion + other.evasion,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct CosmicEgg;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MovingRandomly;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct ChasingPlayer;
+// A monster that can open closed doors, rather than being kept out by them
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OpensDoors;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+// What a monster with a mind of its own is up to
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AiState {
+    // wandering about, or standing guard at its post
+    Idle,
+    // after the player, last seen at this spot
+    Chasing(Point),
+    // lost sight of the player, so off to where they were last seen
+    Investigating(Point),
+    // too badly hurt to fight
+    Fleeing,
+    // back to its post once the excitement is over
+    Returning,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Brain {
+    pub state: AiState,
+    // where a guard keeps to; monsters without a post wander instead
+    pub post: Option<Point>,
+    // runs from the player below this percentage of its max health
+    pub flee_below: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct WantsToMove {
     pub entity: Entity,
     pub destination: Point,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+// Left on a monster whose last move was turned down because someone else was
+// standing on, or also after, the tile it wanted
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MoveBlocked(pub Point);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WantsToDrop {
+    pub entity: Entity,
+    pub item: Entity,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WantsToEquip {
+    pub entity: Entity,
+    pub item: Entity,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WantsToUnequip {
+    pub entity: Entity,
+    pub item: Entity,
+}
+
+// `with` is the item the attack is made with, such as a thrown knife. Without
+// one the attacker's own Attack is used.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct WantsToAttack {
     pub attacker: Entity,
     pub victim: Entity,
+    pub with: Option<Entity>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+// Reaches targets up to `range` tiles away. Given to items that are thrown or
+// zapped, and to monsters that attack from afar.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ranged {
+    pub range: i32,
+}
+
+// Uses left in an item that isn't used up in one go
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Charges(pub i32);
+
+// The player is picking a tile to use `item` on
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Targeting {
+    pub item: Entity,
+    pub cursor: Point,
+}
+
+// The player is on their way somewhere without having to be steered every
+// step: to a tile they clicked, or to wherever is still unexplored
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Travelling {
+    To(Point),
+    Exploring,
+}
+
+// How much experience killing it is worth
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GrantsXp(pub i32);
+
+// The player's level, the experience gathered towards the next one and the
+// perks they have yet to pick
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Experience {
+    pub level: i32,
+    pub xp: i32,
+    pub perks: i32,
+}
+
+impl Experience {
+    pub const XP_PER_LEVEL: i32 = 10;
+    pub const HEALTH_PER_LEVEL: i32 = 4;
+
+    pub fn new() -> Self {
+        Self {
+            level: 1,
+            xp: 0,
+            perks: 0,
+        }
+    }
+
+    // Every level takes a little more experience than the one before
+    pub fn next_level(&self) -> i32 {
+        self.level * Self::XP_PER_LEVEL
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GainXp {
+    pub entity: Entity,
+    pub amount: i32,
+}
+
+// What the player can pick from on reaching a new level
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Perk {
+    Toughness,
+    Might,
+    Precision,
+    Guard,
+    Agility,
+}
+
+impl Perk {
+    pub const ALL: [Perk; 5] = [
+        Perk::Toughness,
+        Perk::Might,
+        Perk::Precision,
+        Perk::Guard,
+        Perk::Agility,
+    ];
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Perk::Toughness => "Toughness: +5 max health",
+            Perk::Might => "Might: +1 damage",
+            Perk::Precision => "Precision: +10 accuracy, +5 crit chance",
+            Perk::Guard => "Guard: +1 armor",
+            Perk::Agility => "Agility: +10 evasion",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Health {
     pub current: i32,
     pub max: i32,
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Name(pub String);
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Description(pub String);
+
+// Damage dealt on a hit is `dice`d`sides` + `bonus`. Accuracy raises the odds
+// of landing a hit, `crit_chance` is the percent chance of a critical one.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Attack {
+    pub dice: i32,
+    pub sides: i32,
+    pub bonus: i32,
+    pub accuracy: i32,
+    pub crit_chance: i32,
+}
+
+impl Attack {
+    pub const UNARMED: Attack = Attack {
+        dice: 1,
+        sides: 1,
+        bonus: 0,
+        accuracy: 0,
+        crit_chance: 0,
+    };
+}
+
+// Armor soaks up damage from every hit, evasion lowers the odds of being hit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Defense {
+    pub armor: i32,
+    pub evasion: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttackOutcome {
+    Miss,
+    Hit(i32),
+    Critical(i32),
+}
+
+impl AttackOutcome {
+    pub fn damage(self) -> i32 {
+        match self {
+            AttackOutcome::Miss => 0,
+            AttackOutcome::Hit(damage) | AttackOutcome::Critical(damage) => damage,
+        }
+    }
+}
+
+// Energy an entity needs before it may act, and what its actions cost
+pub const ACTION_COST: i32 = 100;
+pub const MOVE_COST: i32 = 100;
+pub const ATTACK_COST: i32 = 120;
+pub const USE_ITEM_COST: i32 = 50;
+
+// Energy gained every TIME_STEP. At NORMAL_SPEED an entity acts once per TURN_TIME.
+pub const NORMAL_SPEED: i32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Energy {
+    pub current: i32,
+    pub speed: i32,
+}
+
+impl Energy {
+    pub fn new(speed: i32) -> Self {
+        Self { current: 0, speed }
+    }
+
+    pub fn is_ready(self) -> bool {
+        self.current >= ACTION_COST
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct FieldOfView {
     pub visible_tiles: HashSet<Point>,
     pub radius: i32,
@@ -80,10 +357,149 @@ impl FieldOfView {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ProvidesHealing {
     pub amount: i32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ProvidesDungeonMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InflictsDamage {
+    pub amount: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InflictsConfusion {
+    pub turns: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InflictsFear {
+    pub turns: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InflictsPoison {
+    pub turns: i32,
+    pub damage: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InflictsSlow {
+    pub turns: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProvidesRegeneration {
+    pub turns: i32,
+    pub amount: i32,
+}
+
+// Sends whoever it hits to a random spot on the floor
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProvidesTeleport;
+
+// Moves the user to the tile it was aimed at
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProvidesBlink;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RevealsEntities;
+
+// The item's effects reach everything within `radius` tiles of where it lands
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AreaOfEffect {
+    pub radius: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EffectKind {
+    Heal(i32),
+    Damage(i32),
+    Confuse(i32),
+    Frighten(i32),
+    // turns, then damage or healing every turn
+    Poison(i32, i32),
+    Slow(i32),
+    Regenerate(i32, i32),
+    Teleport,
+    Blink,
+    RevealMap,
+    RevealEntities,
+}
+
+// One effect waiting for the effects system, reaching whatever stands within
+// `radius` tiles of `target`. `source` is whoever set it off.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Effect {
+    pub source: Entity,
+    pub kind: EffectKind,
+    pub target: Point,
+    pub radius: i32,
+}
+
+// Status effects last for `turns` more turns of the game clock
+
+// Stumbles around at random, lashing out at whatever it bumps into, and aims
+// poorly when it does attack
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Confused {
+    pub turns: i32,
+}
+
+// Runs from the player instead of chasing them
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Afraid {
+    pub turns: i32,
+}
+
+// Loses `damage` health every turn, with whoever it was poisoned `by` getting
+// the credit should it die of it
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Poisoned {
+    pub turns: i32,
+    pub damage: i32,
+    pub by: Entity,
+}
+
+// Gains energy at half its usual speed
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Slowed {
+    pub turns: i32,
+}
+
+// Recovers `amount` health every turn
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Regenerating {
+    pub turns: i32,
+    pub amount: i32,
+}
+
+// Shown wherever it is on the floor, in sight or not
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Revealed;
+
+// Health ran out, and the death system is yet to deal with the body. `killer`
+// is whoever dealt the final blow.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Dead {
+    pub killer: Entity,
+}
+
+// What's left of a monster. Drawn beneath anything else on its tile.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Corpse;
+
+// Effects that go off where it dies, like a spore bursting
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OnDeath(pub Vec<EffectKind>);
+
+// How many of each kind of monster it has killed, by name
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Kills(pub BTreeMap<String, i32>);
+
+// How many of each kind of item it has used, thrown or zapped, by name
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ItemsUsed(pub BTreeMap<String, i32>);
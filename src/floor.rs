@@ -0,0 +1,22 @@
+use crate::prelude::*;
+
+// Which floor of the mountain the player has climbed to, counting from the
+// foot at 0. The Cosmic Egg waits on the last one.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Floor(pub u32);
+
+impl Floor {
+    pub const LAST: u32 = 3;
+
+    pub fn is_last(&self) -> bool {
+        self.0 >= Self::LAST
+    }
+
+    pub fn next(&self) -> Self {
+        Self(self.0 + 1)
+    }
+}
+
+// How each floor climbed so far was built, from the foot up
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Layouts(pub Vec<(ArchitectKind, ThemeKind)>);
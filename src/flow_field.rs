@@ -0,0 +1,226 @@
+use crate::prelude::*;
+
+// What a step onto a tile with another monster on it counts for, on top of
+// its distance. Enough that going around is worth it, but not so much that
+// anyone backs away from the player to do so.
+const OCCUPIED_COST: f32 = 2.0;
+
+// How far every tile is from the player, shared by every monster after them.
+// Only worked out again once the player has moved or a door has opened or
+// shut, no matter how many monsters follow it.
+#[derive(Clone, Default)]
+pub struct FlowField {
+    target: Option<Point>,
+    // the map's revision when the distances were worked out
+    revision: u64,
+    diagonals: bool,
+    // the doors its followers can get through
+    doors: Option<DoorState>,
+    distances: Vec<f32>,
+    // how many times it has been worked out, for the benchmark
+    pub rebuilds: u32,
+}
+
+impl FlowField {
+    // Points the field at `target` for followers that can get through doors
+    // up to `doors`, working it out afresh if anything changed
+    pub fn update(&mut self, map: &Map, target: Point, doors: DoorState) {
+        if self.target == Some(target)
+            && self.doors == Some(doors)
+            && self.diagonals == map.diagonals
+            && self.revision == map.revision()
+        {
+            return;
+        }
+
+        let target_idx = map.point2d_to_index(target);
+        let mut dijkstra_map = DijkstraMap::new(
+            SCREEN_WIDTH,
+            SCREEN_HEIGHT,
+            &[target_idx],
+            &map.pathing(doors),
+            1024.0,
+        );
+        // the Dijkstra map leaves its starting point out of the running
+        dijkstra_map.map[target_idx] = 0.0;
+
+        self.target = Some(target);
+        self.revision = map.revision();
+        self.diagonals = map.diagonals;
+        self.doors = Some(doors);
+        self.distances = dijkstra_map.map;
+        self.rebuilds += 1;
+    }
+
+    // The next step downhill from `pos`. Tiles in `occupied` are soft
+    // obstacles: a monster goes around them when it can do so without losing
+    // ground, so a crowd spreads out around the player rather than queuing up
+    // behind the one in front. None if the way on is blocked, or there is none.
+    pub fn next_step(&self, map: &Map, pos: Point, occupied: &[Point]) -> Option<Point> {
+        let here = self
+            .distances
+            .get(map.point2d_to_index(pos))
+            .copied()
+            .filter(|here| *here < f32::MAX)?;
+        let doors = self.doors.unwrap_or(DoorState::Open);
+        let cost = |to: &Point| {
+            let distance = self.distances[map.point2d_to_index(*to)];
+            if occupied.contains(to) {
+                distance + OCCUPIED_COST
+            } else {
+                distance
+            }
+        };
+
+        map.steps()
+            .iter()
+            .filter(|delta| map.can_step_through(pos, **delta, doors))
+            .map(|delta| pos + *delta)
+            .filter(|to| self.distances[map.point2d_to_index(*to)] <= here)
+            .min_by(|a, b| cost(a).partial_cmp(&cost(b)).unwrap())
+            .filter(|to| !occupied.contains(to))
+    }
+}
+
+// Monsters kept out by closed doors and those that open them find their way
+// differently, so each lot gets a flow field of its own
+#[derive(Clone, Default)]
+pub struct FlowFields {
+    kept_out: FlowField,
+    opening_doors: FlowField,
+}
+
+impl FlowFields {
+    pub fn get(&mut self, opens_doors: bool) -> &mut FlowField {
+        if opens_doors {
+            &mut self.opening_doors
+        } else {
+            &mut self.kept_out
+        }
+    }
+
+    #[cfg(test)]
+    pub fn rebuilds(&self) -> u32 {
+        self.kept_out.rebuilds + self.opening_doors.rebuilds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: Point = Point { x: 10, y: 10 };
+
+    fn distance(field: &FlowField, map: &Map, pos: Point) -> f32 {
+        field.distances[map.point2d_to_index(pos)]
+    }
+
+    #[test]
+    fn the_field_is_only_worked_out_again_when_something_changed() {
+        let mut map = Map::new();
+        let mut field = FlowField::default();
+
+        field.update(&map, TARGET, DoorState::Open);
+        field.update(&map, TARGET, DoorState::Open);
+        assert_eq!(field.rebuilds, 1);
+
+        field.update(&map, TARGET + Point::new(1, 0), DoorState::Open);
+        assert_eq!(field.rebuilds, 2, "the target moved");
+
+        field.update(&map, TARGET + Point::new(1, 0), DoorState::Closed);
+        assert_eq!(field.rebuilds, 3, "the followers' doors changed");
+
+        map.diagonals = true;
+        field.update(&map, TARGET + Point::new(1, 0), DoorState::Closed);
+        assert_eq!(field.rebuilds, 4, "diagonals were allowed");
+
+        map.set_door(Point::new(3, 3), DoorState::Closed);
+        field.update(&map, TARGET + Point::new(1, 0), DoorState::Closed);
+        assert_eq!(field.rebuilds, 5, "a door was put in");
+
+        field.update(&map, TARGET + Point::new(1, 0), DoorState::Closed);
+        assert_eq!(field.rebuilds, 5);
+    }
+
+    #[test]
+    fn the_field_leads_to_its_target() {
+        let map = Map::new();
+        let mut field = FlowField::default();
+        field.update(&map, TARGET, DoorState::Open);
+
+        let mut pos = Point::new(2, 5);
+        for _ in 0..20 {
+            match field.next_step(&map, pos, &[]) {
+                Some(next) => pos = next,
+                None => break,
+            }
+        }
+        assert_eq!(pos, TARGET);
+    }
+
+    #[test]
+    fn a_crowd_goes_around_without_losing_ground() {
+        let mut map = Map::new();
+        map.diagonals = true;
+        let mut field = FlowField::default();
+        field.update(&map, TARGET, DoorState::Open);
+
+        let pos = TARGET + Point::new(2, 0);
+        let ahead = TARGET + Point::new(1, 0);
+        assert_eq!(field.next_step(&map, pos, &[]), Some(ahead));
+
+        let next = field.next_step(&map, pos, &[ahead]).unwrap();
+        assert_ne!(next, ahead);
+        assert!(distance(&field, &map, next) < distance(&field, &map, pos));
+    }
+
+    #[test]
+    fn a_crowd_never_steps_onto_an_occupied_tile() {
+        for diagonals in [false, true] {
+            let mut map = Map::new();
+            map.diagonals = diagonals;
+            let mut field = FlowField::default();
+            field.update(&map, TARGET, DoorState::Open);
+
+            let pos = TARGET + Point::new(3, 1);
+            let around: Vec<Point> = map.steps().iter().map(|delta| pos + *delta).collect();
+            // every way of filling the tiles around `pos` with other monsters
+            for mask in 0..1u32 << around.len() {
+                let occupied: Vec<Point> = around
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| mask & 1 << i != 0)
+                    .map(|(_, pos)| *pos)
+                    .collect();
+
+                if let Some(next) = field.next_step(&map, pos, &occupied) {
+                    assert!(
+                        !occupied.contains(&next),
+                        "stepped onto {:?} past {:?}",
+                        next,
+                        occupied
+                    );
+                    assert!(
+                        distance(&field, &map, next) <= distance(&field, &map, pos),
+                        "backed away to {:?} past {:?}",
+                        next,
+                        occupied
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_crowd_waits_when_the_only_way_on_is_taken() {
+        let map = Map::new();
+        let mut field = FlowField::default();
+        field.update(&map, TARGET, DoorState::Open);
+
+        let pos = TARGET + Point::new(2, 0);
+        assert_eq!(
+            field.next_step(&map, pos, &[TARGET + Point::new(1, 0)]),
+            None
+        );
+    }
+}
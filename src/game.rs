@@ -0,0 +1,339 @@
+use crate::prelude::*;
+
+// What came in from the keyboard and mouse this frame, as handed to the
+// systems by Game::tick
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameInput {
+    pub key: Option<VirtualKeyCode>,
+    // what the key does during play and on the screens over it, going by the
+    // keymap
+    pub action: Option<Action>,
+    pub menu_action: Option<Action>,
+    // where the mouse is on screen, and over which tile of the map
+    pub mouse: Point,
+    pub mouse_tile: Point,
+    pub left_click: bool,
+}
+
+impl Default for FrameInput {
+    fn default() -> Self {
+        Self {
+            key: None,
+            action: None,
+            menu_action: None,
+            mouse: Point::zero(),
+            mouse_tile: Point::zero(),
+            left_click: false,
+        }
+    }
+}
+
+// Everything a run needs to play out, without any knowledge of a window.
+// The BTerm frontend in main.rs and the headless driver both sit on top of this.
+pub struct Game {
+    pub ecs: World,
+    pub resources: Resources,
+    pub mode: GameMode,
+    frame_time: f32,
+    input_systems: Schedule,
+    targeting_systems: Schedule,
+    level_up_systems: Schedule,
+    player_systems: Schedule,
+    monster_systems: Schedule,
+    realtime_systems: Schedule,
+}
+
+impl Game {
+    pub fn new(seed: Seed, mode: GameMode, rules: Rules) -> Self {
+        Self::start(seed, mode, rules, None)
+    }
+
+    // A run with every floor laid out the same way, for trying one out
+    pub fn practice(seed: Seed, mode: GameMode, rules: Rules, practice: Practice) -> Self {
+        Self::start(seed, mode, rules, Some(practice))
+    }
+
+    fn start(seed: Seed, mode: GameMode, rules: Rules, practice: Option<Practice>) -> Self {
+        let mut ecs = World::default();
+        let mut resources = Resources::default();
+        let mut rng = RandomNumberGenerator::seeded(seed.0);
+        let mut log = MessageLog::default();
+        let templates = Templates::load_or_defaults(&mut log, &GameClock::default());
+        let floor = Floor::default();
+        let map_builder = build_floor(&mut ecs, &mut rng, &templates, &floor, rules, practice);
+
+        spawn_player(&mut ecs, map_builder.player_start);
+
+        resources.insert(Layouts(vec![(
+            map_builder.architect,
+            map_builder.theme.kind(),
+        )]));
+        resources.insert(map_builder.map);
+        resources.insert(Camera::new(map_builder.player_start));
+        resources.insert(mode.starting_state());
+        resources.insert(mode);
+        resources.insert(rules);
+        resources.insert(map_builder.theme);
+        resources.insert(rng);
+        resources.insert(seed);
+        resources.insert(GameClock::default());
+        resources.insert(floor);
+        resources.insert(templates);
+        if let Some(practice) = practice {
+            resources.insert(practice);
+        }
+
+        log.add(
+            &GameClock::default(),
+            Message::new()
+                .text("You set foot on ")
+                .colored("Mt. Septune", GOLD)
+                .text(". Somewhere up here lies the Cosmic Egg."),
+        );
+        resources.insert(log);
+
+        Self::from_parts(ecs, resources, mode)
+    }
+
+    // Wraps an already populated world, as when restoring a save
+    pub fn from_parts(ecs: World, mut resources: Resources, mode: GameMode) -> Self {
+        // the keys are the player's settings rather than part of the run, so
+        // they're read afresh for restored runs too
+        let keymap = Keymap::load().unwrap_or_else(|e| {
+            let clock = resources.get::<GameClock>().unwrap().clone();
+            if let Some(mut log) = resources.get_mut::<MessageLog>() {
+                let warning = format!("{}; using the default keys.", capitalize(&e));
+                log.add(&clock, Message::new().colored(warning, RED));
+            }
+            Keymap::defaults()
+        });
+        resources.insert(keymap);
+        resources.insert(FrameInput::default());
+        resources.insert(FlowFields::default());
+        resources.insert(Journey::default());
+
+        Self {
+            ecs,
+            resources,
+            mode,
+            frame_time: 0.0,
+            input_systems: build_input_scheduler(),
+            targeting_systems: build_targeting_scheduler(),
+            level_up_systems: build_level_up_scheduler(),
+            player_systems: build_player_scheduler(),
+            monster_systems: build_monster_scheduler(),
+            realtime_systems: build_realtime_scheduler(),
+        }
+    }
+
+    pub fn turn_state(&self) -> TurnState {
+        *self.resources.get::<TurnState>().unwrap()
+    }
+
+    pub fn player_ready(&self) -> bool {
+        <&Energy>::query()
+            .filter(component::<Player>())
+            .iter(&self.ecs)
+            .all(|energy| energy.is_ready())
+    }
+
+    // Advance the game by one frame, `frame_time_ms` after the previous one,
+    // with `key` pressed and the mouse at `mouse` on screen
+    pub fn tick(
+        &mut self,
+        key: Option<VirtualKeyCode>,
+        mouse: Point,
+        left_click: bool,
+        frame_time_ms: f32,
+    ) {
+        let input = {
+            let keymap = self.resources.get::<Keymap>().unwrap();
+            let camera = self.resources.get::<Camera>().unwrap();
+            FrameInput {
+                key,
+                action: key.and_then(|key| keymap.action(key)),
+                menu_action: key.and_then(|key| keymap.menu_action(key)),
+                mouse,
+                mouse_tile: mouse + Point::new(camera.left_x, camera.top_y),
+                left_click,
+            }
+        };
+        self.resources.insert(input);
+
+        // a key or click spent on aiming or picking a perk is done with, and
+        // whatever it led to gets carried out next frame
+        match self.turn_state() {
+            TurnState::Targeting => {
+                self.targeting_systems
+                    .execute(&mut self.ecs, &mut self.resources);
+                return;
+            }
+            TurnState::LevelUp => {
+                self.level_up_systems
+                    .execute(&mut self.ecs, &mut self.resources);
+                return;
+            }
+            _ => {}
+        }
+
+        match self.mode {
+            GameMode::RealTime => self.tick_realtime(frame_time_ms),
+            GameMode::TurnBased => self.tick_turn_based(),
+        }
+
+        if self.turn_state() == TurnState::NextFloor {
+            self.advance_floor();
+        }
+    }
+
+    // Swaps the current floor for a freshly built one further up. The player
+    // and everything they carry or wear come along, the rest is left behind.
+    fn advance_floor(&mut self) {
+        let player = <Entity>::query()
+            .filter(component::<Player>())
+            .iter(&self.ecs)
+            .copied()
+            .next()
+            .unwrap();
+
+        let left_behind: Vec<Entity> = <(Entity, Option<&Carried>, Option<&Equipped>)>::query()
+            .iter(&self.ecs)
+            .filter(|(entity, carried, equipped)| {
+                **entity != player
+                    && carried.is_none_or(|carried| carried.0 != player)
+                    && equipped.is_none_or(|equipped| equipped.0 != player)
+            })
+            .map(|(entity, _, _)| *entity)
+            .collect();
+        for entity in left_behind {
+            self.ecs.remove(entity);
+        }
+
+        let floor = self.resources.get::<Floor>().unwrap().next();
+        let rules = *self.resources.get::<Rules>().unwrap();
+        let practice = self.resources.get::<Practice>().map(|practice| *practice);
+        let map_builder = {
+            let mut rng = self.resources.get_mut::<RandomNumberGenerator>().unwrap();
+            let templates = self.resources.get::<Templates>().unwrap();
+            build_floor(&mut self.ecs, &mut rng, &templates, &floor, rules, practice)
+        };
+
+        if let Some(mut entry) = self.ecs.entry(player) {
+            entry.add_component(map_builder.player_start);
+            // wherever the player was headed is on the floor below
+            entry.remove_component::<Travelling>();
+            let fov = entry.get_component::<FieldOfView>().unwrap().clone_dirty();
+            entry.add_component(fov);
+            // as at the start of the run, the player gets the first move
+            if let Ok(energy) = entry.get_component_mut::<Energy>() {
+                energy.current = ACTION_COST;
+            }
+        }
+
+        let message = if floor.is_last() {
+            Message::new()
+                .text("You reach the summit. ")
+                .colored("The Cosmic Egg", GOLD)
+                .text(" is close.")
+        } else {
+            Message::new().text(format!("You climb to floor {}.", floor.0 + 1))
+        };
+        let clock = self.resources.get::<GameClock>().unwrap().clone();
+        self.resources
+            .get_mut::<MessageLog>()
+            .unwrap()
+            .add(&clock, message);
+
+        self.resources
+            .get_mut::<Layouts>()
+            .unwrap()
+            .0
+            .push((map_builder.architect, map_builder.theme.kind()));
+        self.resources.insert(map_builder.map);
+        // the distances worked out on the floor below are no use up here
+        self.resources.insert(FlowFields::default());
+        self.resources.insert(Journey::default());
+        self.resources.insert(Camera::new(map_builder.player_start));
+        self.resources.insert(map_builder.theme);
+        self.resources.insert(floor);
+        self.resources.insert(self.mode.starting_state());
+    }
+
+    // The player may act on any frame they have the energy for, while the
+    // monster schedule runs a time step for every TIME_STEP millisecs passed
+    fn tick_realtime(&mut self, frame_time_ms: f32) {
+        if self.turn_state() != TurnState::GamePlay {
+            return;
+        }
+
+        self.frame_time += frame_time_ms;
+        self.realtime_systems
+            .execute(&mut self.ecs, &mut self.resources);
+        while self.frame_time > TIME_STEP && self.turn_state() == TurnState::GamePlay {
+            self.frame_time -= TIME_STEP;
+            self.monster_systems
+                .execute(&mut self.ecs, &mut self.resources);
+        }
+    }
+
+    // Queues up an action the player picked outside of the input systems, such
+    // as from the inventory screen, and hands control back to the game to
+    // carry it out
+    pub fn queue_player_action<T: legion::storage::Component>(&mut self, action: T) {
+        self.ecs.push(((), action));
+        self.resources.insert(self.mode.acting_state());
+    }
+
+    // Has the player aim `item` before using it, starting on the closest enemy
+    pub fn begin_targeting(&mut self, player: Entity, item: Entity) {
+        let cursor = visible_enemies(&self.ecs, player)
+            .first()
+            .copied()
+            .unwrap_or_else(|| *self.ecs.entry_ref(player).unwrap().get_component::<Point>().unwrap());
+        if let Some(mut entry) = self.ecs.entry(player) {
+            entry.add_component(Targeting { item, cursor });
+        }
+        self.resources.insert(TurnState::Targeting);
+    }
+
+    // Once the player has acted, time steps are run within the same frame
+    // until they have the energy to act again
+    fn tick_turn_based(&mut self) {
+        if self.turn_state() == TurnState::AwaitingInput {
+            self.input_systems
+                .execute(&mut self.ecs, &mut self.resources);
+        }
+        if self.turn_state() == TurnState::PlayerTurn {
+            self.player_systems
+                .execute(&mut self.ecs, &mut self.resources);
+        }
+        while self.turn_state() == TurnState::MonsterTurn {
+            self.monster_systems
+                .execute(&mut self.ecs, &mut self.resources);
+        }
+    }
+}
+
+// Builds the map for `floor` and spawns everything on it but the player
+fn build_floor(
+    ecs: &mut World,
+    rng: &mut RandomNumberGenerator,
+    templates: &Templates,
+    floor: &Floor,
+    rules: Rules,
+    practice: Option<Practice>,
+) -> MapBuilder {
+    let mut map_builder = MapBuilder::new(rng, floor, practice);
+    map_builder.map.diagonals = rules.diagonals;
+
+    if floor.is_last() {
+        spawn_cosmic_egg(ecs, map_builder.egg_start);
+    } else {
+        let idx = map_builder.map.point2d_to_index(map_builder.egg_start);
+        map_builder.map.tiles[idx] = TileType::Stairs;
+    }
+
+    templates.spawn_entities(ecs, rng, floor, &map_builder.monster_spawns);
+
+    map_builder
+}
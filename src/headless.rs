@@ -0,0 +1,406 @@
+use crate::prelude::*;
+
+// How much time the headless driver pretends passes every frame. Fixed so that
+// a script plays out the same way no matter how fast the host machine is.
+pub const HEADLESS_FRAME_TIME: f32 = 1000.0 / 60.0;
+
+// Drives a Game without a window, feeding it scripted input.
+pub struct Headless {
+    pub game: Game,
+    pub frames: u32,
+}
+
+impl Headless {
+    pub fn new(seed: Seed, mode: GameMode, rules: Rules) -> Self {
+        Self {
+            game: Game::new(seed, mode, rules),
+            frames: 0,
+        }
+    }
+
+    pub fn step(&mut self, key: Option<VirtualKeyCode>) -> TurnState {
+        self.game
+            .tick(key, Point::zero(), false, HEADLESS_FRAME_TIME);
+        self.frames += 1;
+        self.game.turn_state()
+    }
+
+    // Plays a script one key per player action, stopping early if the run ends.
+    // In real-time mode frames idle by until the player has the energy to act.
+    // Letters and digits are pressed as keys, '.' waits and whitespace is ignored.
+    pub fn run_script(&mut self, script: &str) -> Result<TurnState, String> {
+        for c in script.chars().filter(|c| !c.is_whitespace()) {
+            let key = script_key(c).ok_or(format!("no key for '{c}' in script"))?;
+
+            let mut state = self.game.turn_state();
+            while state == TurnState::GamePlay && !self.game.player_ready() {
+                state = self.step(None);
+            }
+            if state != TurnState::GameOver && state != TurnState::Victory {
+                state = self.step(Some(key));
+            }
+
+            if state == TurnState::GameOver || state == TurnState::Victory {
+                return Ok(state);
+            }
+        }
+        Ok(self.game.turn_state())
+    }
+
+    pub fn summary(&self) -> String {
+        let mut player = <(&Point, &Health)>::query().filter(component::<Player>());
+        let (pos, health) = player.iter(&self.game.ecs).next().unwrap();
+        let floor = self.game.resources.get::<Floor>().unwrap().0;
+        format!(
+            "{:?} after {} frames on floor {}, player at ({}, {}) with {}/{} hp",
+            self.game.turn_state(),
+            self.frames,
+            floor + 1,
+            pos.x,
+            pos.y,
+            health.current,
+            health.max
+        )
+    }
+}
+
+fn script_key(c: char) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode as Key;
+    let key = match c.to_ascii_lowercase() {
+        'a' => Key::A,
+        'b' => Key::B,
+        'c' => Key::C,
+        'd' => Key::D,
+        'e' => Key::E,
+        'f' => Key::F,
+        'g' => Key::G,
+        'h' => Key::H,
+        'i' => Key::I,
+        'j' => Key::J,
+        'k' => Key::K,
+        'l' => Key::L,
+        'm' => Key::M,
+        'n' => Key::N,
+        'o' => Key::O,
+        'p' => Key::P,
+        'q' => Key::Q,
+        'r' => Key::R,
+        's' => Key::S,
+        't' => Key::T,
+        'u' => Key::U,
+        'v' => Key::V,
+        'w' => Key::W,
+        'x' => Key::X,
+        'y' => Key::Y,
+        'z' => Key::Z,
+        '0' => Key::Key0,
+        '1' => Key::Key1,
+        '2' => Key::Key2,
+        '3' => Key::Key3,
+        '4' => Key::Key4,
+        '5' => Key::Key5,
+        '6' => Key::Key6,
+        '7' => Key::Key7,
+        '8' => Key::Key8,
+        '9' => Key::Key9,
+        '.' => Key::Period,
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [GameMode; 2] = [GameMode::RealTime, GameMode::TurnBased];
+
+    // An open floor with nothing on it but the player, at full health
+    fn empty_floor(mode: GameMode) -> (Headless, Entity, Point) {
+        let practice = Practice {
+            architect: ArchitectKind::Empty,
+            theme: ThemeKind::Dungeon,
+        };
+        let game = Game::practice(Seed(7), mode, Rules::default(), practice);
+        let mut headless = Headless { game, frames: 0 };
+
+        let others: Vec<Entity> = <Entity>::query()
+            .filter(!component::<Player>())
+            .iter(&headless.game.ecs)
+            .copied()
+            .collect();
+        for entity in others {
+            headless.game.ecs.remove(entity);
+        }
+
+        let (player, pos) = <(Entity, &Point)>::query()
+            .filter(component::<Player>())
+            .iter(&headless.game.ecs)
+            .map(|(entity, pos)| (*entity, *pos))
+            .next()
+            .unwrap();
+        (headless, player, pos)
+    }
+
+    // A monster standing at `pos` that hits as hard as `attack`
+    fn spawn_monster(headless: &mut Headless, pos: Point, hp: i32, attack: Attack) -> Entity {
+        let monster = headless.game.ecs.push((
+            Enemy,
+            pos,
+            Render {
+                color: ColorPair::new(GRAY, BLACK),
+                glyph: to_cp437('r'),
+            },
+            Name("Rat".to_string()),
+            Health {
+                current: hp,
+                max: hp,
+            },
+            FieldOfView::new(6),
+            Energy::new(NORMAL_SPEED),
+        ));
+        let mut entry = headless.game.ecs.entry(monster).unwrap();
+        entry.add_component(attack);
+        entry.add_component(Defense::default());
+        monster
+    }
+
+    fn health(headless: &Headless, entity: Entity) -> i32 {
+        headless
+            .game
+            .ecs
+            .entry_ref(entity)
+            .unwrap()
+            .get_component::<Health>()
+            .unwrap()
+            .current
+    }
+
+    #[test]
+    fn bumping_into_a_monster_attacks_it() {
+        for mode in MODES {
+            let (mut headless, _, pos) = empty_floor(mode);
+            let rat = spawn_monster(&mut headless, pos + Point::new(1, 0), 1000, Attack::UNARMED);
+
+            let state = headless.run_script(&"l".repeat(20)).unwrap();
+
+            assert_eq!(state, mode.starting_state(), "{mode:?}");
+            assert!(
+                health(&headless, rat) < 1000,
+                "{:?}: the rat was never hit",
+                mode
+            );
+        }
+    }
+
+    #[test]
+    fn drinking_a_potion_heals_and_uses_it_up() {
+        for mode in MODES {
+            let (mut headless, player, _) = empty_floor(mode);
+            headless
+                .game
+                .ecs
+                .entry(player)
+                .unwrap()
+                .get_component_mut::<Health>()
+                .unwrap()
+                .current = 5;
+            let potion = headless.game.ecs.push((
+                Item,
+                Carried(player),
+                Render {
+                    color: ColorPair::new(RED, BLACK),
+                    glyph: to_cp437('!'),
+                },
+                Name("Healing potion".to_string()),
+                ProvidesHealing { amount: 6 },
+            ));
+
+            headless.run_script("1").unwrap();
+
+            assert!(health(&headless, player) > 5, "{:?}: no healing", mode);
+            assert!(
+                headless.game.ecs.entry_ref(potion).is_err(),
+                "{:?}: the potion is still there",
+                mode
+            );
+        }
+    }
+
+    #[test]
+    fn picking_up_the_egg_wins_the_run() {
+        for mode in MODES {
+            let (mut headless, _, pos) = empty_floor(mode);
+            spawn_cosmic_egg(&mut headless.game.ecs, pos);
+
+            assert_eq!(headless.run_script("g"), Ok(TurnState::Victory), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn dying_ends_the_run() {
+        for mode in MODES {
+            let (mut headless, player, pos) = empty_floor(mode);
+            headless
+                .game
+                .ecs
+                .entry(player)
+                .unwrap()
+                .get_component_mut::<Health>()
+                .unwrap()
+                .current = 1;
+            let brute = Attack {
+                dice: 1,
+                sides: 1,
+                bonus: 10,
+                accuracy: 1000,
+                crit_chance: 0,
+            };
+            let monster = spawn_monster(&mut headless, pos + Point::new(1, 0), 1000, brute);
+            headless
+                .game
+                .ecs
+                .entry(monster)
+                .unwrap()
+                .add_component(Brain {
+                    state: AiState::Chasing(pos),
+                    post: None,
+                    flee_below: 0,
+                });
+
+            assert_eq!(
+                headless.run_script(&".".repeat(20)),
+                Ok(TurnState::GameOver),
+                "{mode:?}"
+            );
+            assert!(health(&headless, player) < 1, "{:?}", mode);
+        }
+    }
+
+    // The crowd the benchmark sets on the player, how close around them it starts
+    // out and how many turns it gets timed for
+    const BENCH_MONSTERS: usize = 60;
+    const BENCH_SPREAD: i32 = 8;
+    const BENCH_TURNS: u32 = 100;
+    const BENCH_SEED: Seed = Seed(1);
+
+    // Times the monster time steps with a crowd after the player in an open field,
+    // once with the player standing still and once with them walking back and
+    // forth, next to what building a Dijkstra map over the floor costs on its own.
+    // Run it with `cargo test --release benchmark -- --ignored --nocapture`
+    #[test]
+    #[ignore = "a benchmark, only worth running with --release"]
+    fn benchmark() {
+        println!("{BENCH_MONSTERS} monsters chasing the player for {BENCH_TURNS} turns:");
+
+        for (label, moves) in [("standing still", "."), ("walking about", "hl")] {
+            let mut headless = crowd(BENCH_SEED);
+            let steps_before = headless.game.resources.get::<GameClock>().unwrap().steps;
+            let script = moves.repeat(BENCH_TURNS as usize / moves.len());
+
+            let start = std::time::Instant::now();
+            headless.run_script(&script).unwrap();
+            let elapsed = start.elapsed();
+
+            let steps = headless.game.resources.get::<GameClock>().unwrap().steps - steps_before;
+            let rebuilds = headless
+                .game
+                .resources
+                .get::<FlowFields>()
+                .unwrap()
+                .rebuilds();
+            let player_pos = <&Point>::query()
+                .filter(component::<Player>())
+                .iter(&headless.game.ecs)
+                .copied()
+                .next()
+                .unwrap();
+            let around = <&Point>::query()
+                .filter(component::<Enemy>())
+                .iter(&headless.game.ecs)
+                .filter(|pos| DistanceAlg::Pythagoras.distance2d(**pos, player_pos) < 1.5)
+                .count();
+            println!(
+                "  {label}: {steps} time steps at {} µs each, {rebuilds} flow field rebuilds, \
+                 {around} monsters next to the player at the end",
+                elapsed.as_micros() / u128::from(steps.max(1))
+            );
+        }
+
+        let headless = crowd(BENCH_SEED);
+        let map = headless.game.resources.get::<Map>().unwrap();
+        let start_idx = map.point2d_to_index(Point::new(SCREEN_WIDTH / 2, SCREEN_HEIGHT / 2));
+        let start = std::time::Instant::now();
+        for _ in 0..BENCH_TURNS {
+            DijkstraMap::new(SCREEN_WIDTH, SCREEN_HEIGHT, &[start_idx], &*map, 1024.0);
+        }
+        println!(
+            "  one Dijkstra map over the floor: {} µs",
+            (start.elapsed() / BENCH_TURNS).as_micros()
+        );
+    }
+
+    // An open floor with the player at its middle, too tough to die, and
+    // nothing on it but a crowd of monsters that has already spotted them
+    fn crowd(seed: Seed) -> Headless {
+        let mut rng = RandomNumberGenerator::seeded(seed.0);
+        let practice = Practice {
+            architect: ArchitectKind::Empty,
+            theme: ThemeKind::Dungeon,
+        };
+        let mut game = Game::practice(seed, GameMode::TurnBased, Rules::default(), practice);
+
+        let others: Vec<Entity> = <Entity>::query()
+            .filter(!component::<Player>())
+            .iter(&game.ecs)
+            .copied()
+            .collect();
+        for entity in others {
+            game.ecs.remove(entity);
+        }
+
+        let (player_pos, health) = <(&Point, &mut Health)>::query()
+            .filter(component::<Player>())
+            .iter_mut(&mut game.ecs)
+            .next()
+            .unwrap();
+        let player_pos = *player_pos;
+        health.current = i32::MAX / 2;
+        health.max = i32::MAX / 2;
+
+        let mut taken = vec![player_pos];
+        while taken.len() <= BENCH_MONSTERS {
+            let pos = player_pos
+                + Point::new(
+                    rng.range(-BENCH_SPREAD, BENCH_SPREAD + 1),
+                    rng.range(-BENCH_SPREAD, BENCH_SPREAD + 1),
+                );
+            if taken.contains(&pos) {
+                continue;
+            }
+            taken.push(pos);
+            let monster = game.ecs.push((
+                Enemy,
+                pos,
+                Render {
+                    color: ColorPair::new(GRAY, BLACK),
+                    glyph: to_cp437('r'),
+                },
+                Name("Rat".to_string()),
+                Health { current: 1, max: 1 },
+                FieldOfView::new(BENCH_SPREAD * 2),
+                Energy::new(NORMAL_SPEED),
+                Brain {
+                    state: AiState::Chasing(player_pos),
+                    post: None,
+                    flee_below: 0,
+                },
+            ));
+            let mut entry = game.ecs.entry(monster).unwrap();
+            entry.add_component(Attack::UNARMED);
+            entry.add_component(Defense::default());
+        }
+
+        Headless { game, frames: 0 }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::prelude::*;
+
+// Identical items carried by the same owner, listed together on one line.
+// Equipped items are never stacked, and neither are items with different
+// numbers of charges left.
+pub struct ItemStack {
+    pub name: String,
+    pub color: RGB,
+    pub description: Option<String>,
+    pub slot: Option<EquipmentSlot>,
+    pub bonus: Option<StatBonus>,
+    pub range: Option<i32>,
+    pub charges: Option<i32>,
+    pub equipped: bool,
+    pub items: Vec<Entity>,
+}
+
+impl ItemStack {
+    pub fn label(&self) -> String {
+        let name = match self.charges {
+            Some(1) => format!("{} (1 charge)", self.name),
+            Some(charges) => format!("{} ({charges} charges)", self.name),
+            None => self.name.clone(),
+        };
+        if self.equipped {
+            format!("{name} (equipped)")
+        } else if self.items.len() > 1 {
+            format!("{name} x{}", self.items.len())
+        } else {
+            name
+        }
+    }
+}
+
+// Everything `owner` carries, with items that share a name and the charges
+// they have left stacked together
+pub fn carried_stacks<W: EntityStore>(ecs: &W, owner: Entity) -> Vec<ItemStack> {
+    let mut stacks: Vec<ItemStack> = Vec::new();
+
+    <(Entity, &Carried)>::query()
+        .filter(component::<Item>())
+        .iter(ecs)
+        .filter(|(_, carried)| carried.0 == owner)
+        .for_each(|(entity, _)| {
+            let stack = item_stack(ecs, *entity, false);
+            let same =
+                |other: &&mut ItemStack| other.name == stack.name && other.charges == stack.charges;
+            match stacks.iter_mut().find(same) {
+                Some(other) => other.items.push(*entity),
+                None => stacks.push(stack),
+            }
+        });
+
+    stacks
+}
+
+// What `owner` is wearing or wielding, one item per stack
+pub fn equipped_items<W: EntityStore>(ecs: &W, owner: Entity) -> Vec<ItemStack> {
+    <(Entity, &Equipped)>::query()
+        .iter(ecs)
+        .filter(|(_, equipped)| equipped.0 == owner)
+        .map(|(entity, _)| item_stack(ecs, *entity, true))
+        .collect()
+}
+
+fn item_stack<W: EntityStore>(ecs: &W, item: Entity, equipped: bool) -> ItemStack {
+    let entry = ecs.entry_ref(item).unwrap();
+    ItemStack {
+        name: entry
+            .get_component::<Name>()
+            .map_or_else(|_| "something".to_string(), |name| name.0.clone()),
+        color: entry
+            .get_component::<Render>()
+            .map_or(RGB::named(WHITE), |render| render.color.fg.to_rgb()),
+        description: entry
+            .get_component::<Description>()
+            .ok()
+            .map(|description| description.0.clone()),
+        slot: entry
+            .get_component::<Equippable>()
+            .ok()
+            .map(|equippable| equippable.slot),
+        bonus: entry.get_component::<StatBonus>().ok().copied(),
+        range: entry
+            .get_component::<Ranged>()
+            .ok()
+            .map(|ranged| ranged.range),
+        charges: entry
+            .get_component::<Charges>()
+            .ok()
+            .map(|charges| charges.0),
+        equipped,
+        items: vec![item],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn carry(ecs: &mut World, owner: Entity, name: &str, charges: Option<i32>) -> Entity {
+        let item = ecs.push((Item, Carried(owner), Name(name.to_string())));
+        if let Some(charges) = charges {
+            ecs.entry(item).unwrap().add_component(Charges(charges));
+        }
+        item
+    }
+
+    fn labels(ecs: &World, owner: Entity) -> Vec<String> {
+        let mut labels: Vec<String> = carried_stacks(ecs, owner)
+            .iter()
+            .map(ItemStack::label)
+            .collect();
+        labels.sort();
+        labels
+    }
+
+    #[test]
+    fn items_with_the_same_name_stack() {
+        let mut ecs = World::default();
+        let player = ecs.push(());
+        let rat = ecs.push(());
+        carry(&mut ecs, player, "Healing potion", None);
+        carry(&mut ecs, player, "Healing potion", None);
+        carry(&mut ecs, player, "Dungeon map", None);
+        carry(&mut ecs, rat, "Healing potion", None);
+
+        assert_eq!(labels(&ecs, player), ["Dungeon map", "Healing potion x2"]);
+    }
+
+    #[test]
+    fn items_with_different_charges_left_stay_apart() {
+        let mut ecs = World::default();
+        let player = ecs.push(());
+        let full = carry(&mut ecs, player, "Wand", Some(3));
+        carry(&mut ecs, player, "Wand", Some(3));
+        let used = carry(&mut ecs, player, "Wand", Some(1));
+
+        assert_eq!(
+            labels(&ecs, player),
+            ["Wand (1 charge)", "Wand (3 charges) x2"]
+        );
+        let stacks = carried_stacks(&ecs, player);
+        let items = |charges| {
+            let stack = stacks.iter().find(|stack| stack.charges == Some(charges));
+            stack.unwrap().items.clone()
+        };
+        assert!(items(3).contains(&full));
+        assert_eq!(items(1), [used]);
+    }
+}
@@ -0,0 +1,52 @@
+use crate::prelude::*;
+
+// The player's way across the floor while travelling, kept from one step to
+// the next. The route is only worked out again once the map or the places
+// being headed for change, and the monsters that were already in view when
+// the player set off don't bring them to a halt.
+#[derive(Default)]
+pub struct Journey {
+    // None until the travel system has taken its first step
+    pub seen: Option<Vec<Entity>>,
+    targets: Vec<usize>,
+    revision: u64,
+    route: Option<DijkstraMap>,
+}
+
+impl Journey {
+    // The first step on the shortest way from `from` to any of the `targets`.
+    // The way can lead through doors, which the player opens by walking into
+    // them.
+    pub fn next_step(&mut self, map: &Map, from: Point, targets: Vec<usize>) -> Option<Point> {
+        let pathing = map.pathing(DoorState::Locked);
+        if self.route.is_none() || self.targets != targets || self.revision != map.revision() {
+            self.route = Some(DijkstraMap::new(
+                SCREEN_WIDTH,
+                SCREEN_HEIGHT,
+                &targets,
+                &pathing,
+                1024.0,
+            ));
+            self.targets = targets;
+            self.revision = map.revision();
+        }
+        let route = self.route.as_ref()?;
+
+        let from_idx = map.point2d_to_index(from);
+        // the targets themselves are left at the far end of the Dijkstra map,
+        // so one right next to the player gets stepped onto directly
+        pathing
+            .get_available_exits(from_idx)
+            .iter()
+            .map(|(idx, _)| *idx)
+            .find(|idx| self.targets.contains(idx))
+            .or_else(|| {
+                if route.map[from_idx] < f32::MAX {
+                    DijkstraMap::find_lowest_exit(route, from_idx, &pathing)
+                } else {
+                    None
+                }
+            })
+            .map(|idx| map.index_to_point2d(idx))
+    }
+}
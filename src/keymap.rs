@@ -0,0 +1,248 @@
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+
+const KEYMAP_PATH: &str = "resources/keymap.ron";
+// The keymap the game ships with, to fall back on should the file be missing
+// or broken
+const DEFAULT_KEYMAP: &str = include_str!("../resources/keymap.ron");
+
+// Everything the player can do with a key press, during play or on one of the
+// screens shown over it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Action {
+    MoveWest,
+    MoveEast,
+    MoveNorth,
+    MoveSouth,
+    MoveNorthWest,
+    MoveNorthEast,
+    MoveSouthWest,
+    MoveSouthEast,
+    Wait,
+    // walk towards the nearest unexplored part of the floor until something
+    // turns up
+    Explore,
+    PickUp,
+    // shut an open door next to the player
+    CloseDoor,
+    // the inventory stack to use, counting from 1
+    UseSlot(usize),
+    Inventory,
+    MessageLog,
+    SaveAndQuit,
+    // while aiming
+    NextTarget,
+    Fire,
+    // for testing: takes 5 health off the player
+    HurtSelf,
+    // on the menus, the inventory and the other screens
+    MenuUp,
+    MenuDown,
+    PageUp,
+    PageDown,
+    MenuLeft,
+    MenuRight,
+    Select,
+    // use, equip or take off the item picked in the inventory
+    Use,
+    Drop,
+    // the perk on offer to pick on levelling up, counting from 1
+    Perk(usize),
+}
+
+impl Action {
+    // Where a move takes the player, if it is one
+    pub fn delta(self) -> Option<Point> {
+        match self {
+            Action::MoveWest => Some(Point::new(-1, 0)),
+            Action::MoveEast => Some(Point::new(1, 0)),
+            Action::MoveNorth => Some(Point::new(0, -1)),
+            Action::MoveSouth => Some(Point::new(0, 1)),
+            Action::MoveNorthWest => Some(Point::new(-1, -1)),
+            Action::MoveNorthEast => Some(Point::new(1, -1)),
+            Action::MoveSouthWest => Some(Point::new(-1, 1)),
+            Action::MoveSouthEast => Some(Point::new(1, 1)),
+            _ => None,
+        }
+    }
+
+    // Whether it is done on a menu or other screen rather than during play
+    pub fn on_menus(self) -> bool {
+        matches!(
+            self,
+            Action::MenuUp
+                | Action::MenuDown
+                | Action::PageUp
+                | Action::PageDown
+                | Action::MenuLeft
+                | Action::MenuRight
+                | Action::Select
+                | Action::Use
+                | Action::Drop
+                | Action::Perk(_)
+        )
+    }
+}
+
+// Which key does what, as set in the keymap file. A key can do one thing
+// during play and another on the menus.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Keymap {
+    play: HashMap<VirtualKeyCode, Action>,
+    menus: HashMap<VirtualKeyCode, Action>,
+}
+
+impl Keymap {
+    pub fn load() -> Result<Self, String> {
+        let file =
+            File::open(KEYMAP_PATH).map_err(|e| format!("could not open {KEYMAP_PATH}: {e}"))?;
+        let bindings: Vec<(Action, Vec<String>)> = ron::de::from_reader(file)
+            .map_err(|e| format!("could not parse {KEYMAP_PATH}: {e}"))?;
+        Self::from_bindings(&bindings).map_err(|e| format!("{KEYMAP_PATH}: {e}"))
+    }
+
+    // The bindings the game ships with
+    pub fn defaults() -> Self {
+        let bindings: Vec<(Action, Vec<String>)> =
+            ron::de::from_str(DEFAULT_KEYMAP).expect("the default keymap does not parse");
+        Self::from_bindings(&bindings).expect("the default keymap does not bind")
+    }
+
+    // Every action with the names of the keys that set it off
+    pub fn from_bindings(bindings: &[(Action, Vec<String>)]) -> Result<Self, String> {
+        let mut keymap = Self::default();
+        for (action, names) in bindings {
+            let keys = if action.on_menus() {
+                &mut keymap.menus
+            } else {
+                &mut keymap.play
+            };
+            for name in names {
+                let key = key_named(name).ok_or(format!("there is no key called '{name}'"))?;
+                if let Some(other) = keys.insert(key, *action) {
+                    return Err(format!(
+                        "'{name}' is bound to both {other:?} and {action:?}"
+                    ));
+                }
+            }
+        }
+        Ok(keymap)
+    }
+
+    // What `key` does during play
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.play.get(&key).copied()
+    }
+
+    // What `key` does on the menus and other screens
+    pub fn menu_action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.menus.get(&key).copied()
+    }
+
+    // The key to show for `action`. Of several bound to it, a letter is
+    // picked over a number and a number over anything else.
+    pub fn key_for(&self, action: Action) -> Option<VirtualKeyCode> {
+        let keys = if action.on_menus() {
+            &self.menus
+        } else {
+            &self.play
+        };
+        BINDABLE
+            .iter()
+            .copied()
+            .find(|key| keys.get(key) == Some(&action))
+    }
+
+    // The name of the key to show for `action`, for help lines that have to
+    // mention it whether it is bound or not
+    pub fn key_name(&self, action: Action) -> String {
+        self.key_for(action)
+            .map_or_else(|| "(unbound)".to_string(), |key| format!("{key:?}"))
+    }
+}
+
+// The keys that can be bound, going by the names they print with
+#[rustfmt::skip]
+const BINDABLE: &[VirtualKeyCode] = {
+    use VirtualKeyCode as Key;
+    &[
+        Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I,
+        Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R,
+        Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+        Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4,
+        Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+        Key::Numpad0, Key::Numpad1, Key::Numpad2, Key::Numpad3, Key::Numpad4,
+        Key::Numpad5, Key::Numpad6, Key::Numpad7, Key::Numpad8, Key::Numpad9,
+        Key::Left, Key::Right, Key::Up, Key::Down,
+        Key::Home, Key::End, Key::PageUp, Key::PageDown, Key::Insert, Key::Delete,
+        Key::Space, Key::Return, Key::Tab, Key::Back,
+        Key::Period, Key::Comma, Key::Semicolon, Key::Apostrophe, Key::Slash,
+        Key::Backslash, Key::Minus, Key::Equals, Key::LBracket, Key::RBracket, Key::Grave,
+        Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6,
+        Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12,
+    ]
+};
+
+fn key_named(name: &str) -> Option<VirtualKeyCode> {
+    BINDABLE
+        .iter()
+        .copied()
+        .find(|key| format!("{key:?}").eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bindings(pairs: &[(Action, &[&str])]) -> Vec<(Action, Vec<String>)> {
+        pairs
+            .iter()
+            .map(|(action, keys)| (*action, keys.iter().map(|key| (*key).to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn a_key_bound_twice_is_refused() {
+        let keymap = Keymap::from_bindings(&bindings(&[
+            (Action::MoveWest, &["H"]),
+            (Action::Wait, &["Space", "H"]),
+        ]));
+        assert!(keymap.is_err());
+    }
+
+    #[test]
+    fn a_key_can_do_one_thing_in_play_and_another_on_the_menus() {
+        let keymap = Keymap::from_bindings(&bindings(&[
+            (Action::MoveNorth, &["K"]),
+            (Action::MenuUp, &["K"]),
+        ]))
+        .unwrap();
+        assert_eq!(keymap.action(VirtualKeyCode::K), Some(Action::MoveNorth));
+        assert_eq!(keymap.menu_action(VirtualKeyCode::K), Some(Action::MenuUp));
+    }
+
+    #[test]
+    fn rebinding_a_key_takes_effect() {
+        let keymap = Keymap::from_bindings(&bindings(&[(Action::Wait, &["W"])])).unwrap();
+        assert_eq!(keymap.action(VirtualKeyCode::W), Some(Action::Wait));
+        assert_eq!(keymap.action(VirtualKeyCode::Space), None);
+        assert_eq!(keymap.key_for(Action::Wait), Some(VirtualKeyCode::W));
+    }
+
+    #[test]
+    fn an_unknown_key_is_refused() {
+        let keymap = Keymap::from_bindings(&bindings(&[(Action::Wait, &["Hyper"])]));
+        assert!(keymap.is_err());
+    }
+
+    #[test]
+    fn the_default_keymap_binds() {
+        let keymap = Keymap::defaults();
+        assert_eq!(keymap.action(VirtualKeyCode::O), Some(Action::Explore));
+        assert_eq!(
+            keymap.menu_action(VirtualKeyCode::Return),
+            Some(Action::Select)
+        );
+    }
+}
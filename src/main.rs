@@ -1,8 +1,21 @@
 #![warn(clippy::pedantic)]
 mod camera;
+mod cli;
 mod components;
+mod floor;
+mod flow_field;
+mod game;
+mod headless;
+mod inventory;
+mod journey;
+mod keymap;
 mod map;
 mod map_builder;
+mod menu;
+mod message_log;
+mod morgue;
+mod save;
+mod seed;
 mod spawner;
 mod systems;
 mod turn_state;
@@ -14,11 +27,23 @@ mod prelude {
     pub const DISPLAY_WIDTH: i32 = SCREEN_WIDTH;
     pub const DISPLAY_HEIGHT: i32 = SCREEN_HEIGHT;
     pub const TURN_TIME: f32 = 200.0; // frame duration in millisecs
+    pub const TIME_STEP: f32 = TURN_TIME / 10.0; // energy tick in millisecs
 
     pub use crate::camera::*;
     pub use crate::components::*;
+    pub use crate::floor::*;
+    pub use crate::flow_field::*;
+    pub use crate::game::*;
+    pub use crate::headless::*;
+    pub use crate::inventory::*;
+    pub use crate::journey::*;
+    pub use crate::keymap::*;
     pub use crate::map::*;
     pub use crate::map_builder::*;
+    pub use crate::message_log::*;
+    pub use crate::morgue::*;
+    pub use crate::save::*;
+    pub use crate::seed::*;
     pub use crate::spawner::*;
     pub use crate::systems::*;
     pub use crate::turn_state::*;
@@ -26,48 +51,319 @@ mod prelude {
     pub use legion::systems::CommandBuffer;
     pub use legion::world::SubWorld;
     pub use legion::*;
+
+    pub use serde::{Deserialize, Serialize};
 }
 
 use prelude::*;
+use std::path::Path;
 
 struct State {
-    ecs: World,
-    resources: Resources,
-    frame_time: f32,
-    monster_systems: Schedule,
-    realtime_systems: Schedule,
+    game: Game,
     render_systems: Schedule,
+    history_scroll: usize,
+    inventory_cursor: usize,
+    // written down once the run is over, for the end screens to show
+    record: Option<RunRecord>,
+    menu_cursor: usize,
+    // shown under a menu, such as why a save could not be loaded
+    menu_message: Option<String>,
+    seed_entry: String,
+    practice: Practice,
+    // the screen the options return to
+    options_from: TurnState,
+    // what new runs are started in
+    mode: GameMode,
+    rules: Rules,
 }
 
 impl State {
-    fn new() -> Self {
-        let mut ecs = World::default();
-        let mut resources = Resources::default();
-        let mut rng = RandomNumberGenerator::new();
-        let map_builder = MapBuilder::new(&mut rng);
-
-        spawn_player(&mut ecs, map_builder.player_start);
-        spawn_cosmic_egg(&mut ecs, map_builder.egg_start);
-
-        map_builder
-            .monster_spawns
-            .iter()
-            .for_each(|pos| spawn_entity(&mut ecs, &mut rng, *pos));
-
-        resources.insert(map_builder.map);
-        resources.insert(Camera::new(map_builder.player_start));
-        // resources.insert(TurnState::AwaitingInput);
-        resources.insert(TurnState::GamePlay);
-        resources.insert(map_builder.theme);
+    fn new(seed: Seed, mode: GameMode, rules: Rules) -> Self {
+        Self::from_game(Game::new(seed, mode, rules))
+    }
+
+    // The title menu, with a run from `seed` ready to go behind it
+    fn title(seed: Seed, mode: GameMode, rules: Rules) -> Self {
+        let mut state = Self::new(seed, mode, rules);
+        state.open(TurnState::MainMenu);
+        state
+    }
 
+    fn from_game(game: Game) -> Self {
+        let mode = game.mode;
+        let rules = *game.resources.get::<Rules>().unwrap();
         Self {
-            ecs,
-            resources,
-            frame_time: 0.0,
-            monster_systems: build_monster_scheduler(),
-            realtime_systems: build_realtime_scheduler(),
+            game,
             render_systems: build_render_scheduler(),
+            history_scroll: 0,
+            inventory_cursor: 0,
+            record: None,
+            menu_cursor: 0,
+            menu_message: None,
+            seed_entry: String::new(),
+            practice: Practice {
+                architect: ArchitectKind::DungeonRooms,
+                theme: ThemeKind::Dungeon,
+            },
+            options_from: TurnState::MainMenu,
+            mode,
+            rules,
+        }
+    }
+
+    // Saving is only offered while the player is free to act, so nothing is
+    // ever caught halfway through a turn
+    fn save_and_quit(&mut self, ctx: &mut BTerm) {
+        match save_game(&self.game, Path::new(SAVE_PATH)) {
+            Ok(()) => ctx.quit(),
+            Err(e) => {
+                let clock = self.game.resources.get::<GameClock>().unwrap().clone();
+                let mut log = self.game.resources.get_mut::<MessageLog>().unwrap();
+                log.add(&clock, Message::new().colored(capitalize(&e), RED));
+            }
+        }
+    }
+
+    // What `key` does on the menus and other screens
+    fn menu_action(&self, key: Option<VirtualKeyCode>) -> Option<Action> {
+        let keymap = self.game.resources.get::<Keymap>().unwrap();
+        key.and_then(|key| keymap.menu_action(key))
+    }
+
+    // The key bound to `action`, to go in a help line
+    fn key_name(&self, action: Action) -> String {
+        self.game
+            .resources
+            .get::<Keymap>()
+            .unwrap()
+            .key_name(action)
+    }
+
+    fn message_history(&mut self, ctx: &mut BTerm) {
+        const PAGE: usize = (SCREEN_HEIGHT - 6) as usize;
+
+        let help = format!(
+            "{}/{}, {}/{} to scroll - ESC to return",
+            self.key_name(Action::MenuUp),
+            self.key_name(Action::MenuDown),
+            self.key_name(Action::PageUp),
+            self.key_name(Action::PageDown),
+        );
+        let action = self.menu_action(ctx.key);
+        let log = self.game.resources.get::<MessageLog>().unwrap();
+        let max_scroll = log.entries.len().saturating_sub(PAGE);
+
+        match action {
+            Some(Action::MenuUp) => self.history_scroll += 1,
+            Some(Action::MenuDown) => {
+                self.history_scroll = self.history_scroll.saturating_sub(1);
+            }
+            Some(Action::PageUp) => self.history_scroll += PAGE,
+            Some(Action::PageDown) => {
+                self.history_scroll = self.history_scroll.saturating_sub(PAGE);
+            }
+            _ => {}
+        }
+        self.history_scroll = usize::min(self.history_scroll, max_scroll);
+
+        let mut draw_batch = DrawBatch::new();
+        draw_batch.target(2);
+        draw_batch.print_color_centered(1, "MESSAGE LOG", ColorPair::new(GOLD, BLACK));
+
+        // scrolling counts back from the newest message
+        let end = log.entries.len() - self.history_scroll;
+        let start = end.saturating_sub(PAGE);
+        for (y, entry) in (3..).zip(&log.entries[start..end]) {
+            draw_log_entry(&mut draw_batch, Point::new(1, y), entry);
+        }
+
+        draw_batch.print_color_centered(SCREEN_HEIGHT - 2, help, ColorPair::new(GREEN, BLACK));
+        draw_batch.submit(20000).expect("Message log Batch Error");
+        drop(log);
+
+        if let Some(VirtualKeyCode::Escape) = ctx.key {
+            self.history_scroll = 0;
+            self.game.resources.insert(self.game.mode.starting_state());
+        }
+    }
+
+    fn inventory(&mut self, ctx: &mut BTerm) {
+        const PAGE: usize = (SCREEN_HEIGHT - 14) as usize;
+
+        let player = *<Entity>::query()
+            .filter(component::<Player>())
+            .iter(&self.game.ecs)
+            .next()
+            .unwrap();
+        let mut stacks = equipped_items(&self.game.ecs, player);
+        stacks.extend(carried_stacks(&self.game.ecs, player));
+        let ready = self.game.player_ready();
+        let action = self.menu_action(ctx.key);
+
+        match action {
+            Some(Action::MenuUp) => {
+                self.inventory_cursor = self.inventory_cursor.saturating_sub(1);
+            }
+            Some(Action::MenuDown) => self.inventory_cursor += 1,
+            Some(Action::PageUp) => {
+                self.inventory_cursor = self.inventory_cursor.saturating_sub(PAGE);
+            }
+            Some(Action::PageDown) => self.inventory_cursor += PAGE,
+            _ => {}
+        }
+        self.inventory_cursor = usize::min(self.inventory_cursor, stacks.len().saturating_sub(1));
+
+        let mut draw_batch = DrawBatch::new();
+        draw_batch.target(2);
+        draw_batch.print_color_centered(1, "INVENTORY", ColorPair::new(GOLD, BLACK));
+
+        if stacks.is_empty() {
+            draw_batch.print_color_centered(
+                4,
+                "You are not carrying anything.",
+                ColorPair::new(GRAY, BLACK),
+            );
+        }
+
+        // the list scrolls along to keep the selected stack in view
+        let first = self.inventory_cursor.saturating_sub(PAGE - 1);
+        for (y, (i, stack)) in (3..).zip(stacks.iter().enumerate().skip(first).take(PAGE)) {
+            let bg = if i == self.inventory_cursor {
+                DARK_SLATE
+            } else {
+                BLACK
+            };
+            draw_batch.print_color(
+                Point::new(2, y),
+                stack.label(),
+                ColorPair::new(stack.color, bg),
+            );
+        }
+
+        if let Some(stack) = stacks.get(self.inventory_cursor) {
+            draw_batch.print_color(
+                Point::new(2, SCREEN_HEIGHT - 9),
+                &stack.name,
+                ColorPair::new(stack.color, BLACK),
+            );
+            let description = stack
+                .description
+                .as_deref()
+                .unwrap_or("Nothing remarkable about it.");
+            draw_batch.print_color(
+                Point::new(2, SCREEN_HEIGHT - 8),
+                description,
+                ColorPair::new(WHITE, BLACK),
+            );
+            if let Some(slot) = stack.slot {
+                let bonus = stack.bonus.unwrap_or_default().summary();
+                draw_batch.print_color(
+                    Point::new(2, SCREEN_HEIGHT - 7),
+                    format!("{slot:?}: {bonus}"),
+                    ColorPair::new(GRAY, BLACK),
+                );
+            }
+        }
+
+        let help = if ready {
+            format!(
+                "{}/{} to select - {}/{} to use, equip or take off - {} to drop - ESC to return",
+                self.key_name(Action::MenuUp),
+                self.key_name(Action::MenuDown),
+                self.key_name(Action::Use),
+                self.key_name(Action::Select),
+                self.key_name(Action::Drop),
+            )
+        } else {
+            "You need a moment before you can act - ESC to return".to_string()
+        };
+        draw_batch.print_color_centered(SCREEN_HEIGHT - 2, help, ColorPair::new(GREEN, BLACK));
+        draw_batch.submit(20000).expect("Inventory Batch Error");
+
+        if let (true, Some(stack)) = (ready, stacks.get(self.inventory_cursor)) {
+            self.inventory_action(action, player, stack);
+        }
+
+        if let Some(VirtualKeyCode::Escape) = ctx.key {
+            self.game.resources.insert(self.game.mode.starting_state());
+        }
+    }
+
+    // Queues whatever the pressed key asks to be done with the selected stack
+    fn inventory_action(&mut self, action: Option<Action>, player: Entity, stack: &ItemStack) {
+        let item = stack.items[0];
+        match action {
+            Some(Action::Use | Action::Select) if stack.equipped => {
+                self.game.queue_player_action(WantsToUnequip {
+                    entity: player,
+                    item,
+                });
+            }
+            Some(Action::Use | Action::Select) if stack.range.is_some() => {
+                self.game.begin_targeting(player, item);
+            }
+            Some(Action::Use | Action::Select) if stack.slot.is_some() => {
+                self.game.queue_player_action(WantsToEquip {
+                    entity: player,
+                    item,
+                });
+            }
+            Some(Action::Use | Action::Select) => {
+                self.game.queue_player_action(ActivateItem {
+                    used_by: player,
+                    item,
+                    target: None,
+                });
+            }
+            Some(Action::Drop) => {
+                self.game.queue_player_action(WantsToDrop {
+                    entity: player,
+                    item,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    // The perks on offer; picking one is left to the game's level up systems
+    fn level_up(&self) {
+        let experience = <&Experience>::query()
+            .filter(component::<Player>())
+            .iter(&self.game.ecs)
+            .copied()
+            .next();
+
+        let mut draw_batch = DrawBatch::new();
+        draw_batch.target(2);
+        if let Some(experience) = experience {
+            draw_batch.print_color_centered(
+                2,
+                format!("YOU REACH LEVEL {}", experience.level),
+                ColorPair::new(GOLD, BLACK),
+            );
+            draw_batch.print_color_centered(
+                4,
+                "The climb hardens you. Choose how:",
+                ColorPair::new(WHITE, BLACK),
+            );
+        }
+        for (y, (n, perk)) in (7..).step_by(2).zip((1..).zip(Perk::ALL.iter())) {
+            draw_batch.print_color(
+                Point::new(SCREEN_WIDTH / 2 - 20, y),
+                format!(
+                    "{} : {}",
+                    self.key_name(Action::Perk(n)),
+                    perk.description()
+                ),
+                ColorPair::new(WHITE, BLACK),
+            );
         }
+        draw_batch.print_color_centered(
+            SCREEN_HEIGHT - 4,
+            "Press the key next to a perk to pick it.",
+            ColorPair::new(GREEN, BLACK),
+        );
+        draw_batch.submit(20000).expect("Level Up Batch Error");
     }
 
     fn game_over(&mut self, ctx: &mut BTerm) {
@@ -95,6 +391,9 @@ impl State {
             "------------------",
         );
 
+        self.print_run_record(ctx, 13);
+        self.print_seed(ctx);
+
         ctx.print_color_centered(SCREEN_HEIGHT - 4, GREEN, BLACK, "Press ESC to try again.");
 
         if let Some(VirtualKeyCode::Escape) = ctx.key {
@@ -120,6 +419,9 @@ impl State {
         );
         ctx.print_color_centered(7, WHITE, BLACK, "through every fiber of your being.");
 
+        self.print_run_record(ctx, 11);
+        self.print_seed(ctx);
+
         ctx.print_color_centered(SCREEN_HEIGHT - 5, GREEN, BLACK, "Press ESC to start again.");
 
         if let Some(VirtualKeyCode::Escape) = ctx.key {
@@ -127,8 +429,61 @@ impl State {
         }
     }
 
+    // How the run went and where it placed among the best ones, from row `top`
+    // down. The run is written to the morgue file the first time round.
+    fn print_run_record(&mut self, ctx: &mut BTerm, top: i32) {
+        let game = &self.game;
+        let record = self.record.get_or_insert_with(|| RunRecord::write(game));
+        let summary = &record.summary;
+
+        ctx.print_color_centered(
+            top,
+            WHITE,
+            BLACK,
+            format!(
+                "{} after {} turns. Score: {}",
+                summary.cause,
+                summary.turns,
+                summary.score()
+            ),
+        );
+        match &record.error {
+            Some(e) => ctx.print_color_centered(top + 1, RED, BLACK, capitalize(e)),
+            None => ctx.print_color_centered(
+                top + 1,
+                GRAY,
+                BLACK,
+                format!("The run was written to {MORGUE_PATH}."),
+            ),
+        }
+
+        ctx.print_color_centered(top + 3, GOLD, BLACK, "HIGH SCORES");
+        for (y, (i, entry)) in (top + 5..).zip(record.high_scores.0.iter().enumerate()) {
+            let color = if record.place == Some(i) { GOLD } else { WHITE };
+            ctx.print_color(
+                SCREEN_WIDTH / 2 - 45,
+                y,
+                color,
+                BLACK,
+                format!(
+                    "{:>2}. {:>6}  {:<40} {:>6} turns  seed {}",
+                    i + 1,
+                    entry.score,
+                    entry.cause,
+                    entry.turns,
+                    entry.seed
+                ),
+            );
+        }
+    }
+
+    fn print_seed(&self, ctx: &mut BTerm) {
+        let seed = self.game.resources.get::<Seed>().unwrap().0;
+        ctx.print_color_centered(SCREEN_HEIGHT - 7, GRAY, BLACK, format!("Seed: {seed}"));
+    }
+
     fn reset_game_state(&mut self) {
-        *self = State::new();
+        *self = State::title(Seed::random(), self.mode, self.rules);
     }
 }
 
@@ -145,57 +500,74 @@ impl GameState for State {
         ctx.set_active_console(2);
         ctx.cls();
 
-        self.frame_time += ctx.frame_time_ms;
-        // -- Execute systems
-        self.resources.insert(ctx.key);
+        let action = ctx
+            .key
+            .and_then(|key| self.game.resources.get::<Keymap>().unwrap().action(key));
+        if action == Some(Action::SaveAndQuit)
+            && self.game.turn_state() == self.game.mode.starting_state()
+        {
+            self.save_and_quit(ctx);
+            return;
+        }
+        if ctx.key == Some(VirtualKeyCode::Escape)
+            && self.game.turn_state() == self.game.mode.starting_state()
+        {
+            // the pause menu takes over from the next frame, so the same
+            // press doesn't close it again
+            self.open(TurnState::Paused);
+            ctx.key = None;
+        }
 
         ctx.set_active_console(0);
-        self.resources.insert(Point::from_tuple(ctx.mouse_pos()));
+        let mouse = Point::from_tuple(ctx.mouse_pos());
 
-        let current_state = self.resources.get::<TurnState>().unwrap().clone();
+        // -- Execute systems
+        self.game
+            .tick(ctx.key, mouse, ctx.left_click, ctx.frame_time_ms);
 
-        match current_state {
-            TurnState::GamePlay => {
-                self.realtime_systems
-                    .execute(&mut self.ecs, &mut self.resources);
+        match self.game.turn_state() {
+            TurnState::MainMenu => {
+                self.main_menu(ctx);
+            }
+            TurnState::SeedEntry => {
+                self.seed_menu(ctx);
+            }
+            TurnState::PracticeMenu => {
+                self.practice_menu(ctx);
+            }
+            TurnState::Options => {
+                self.options(ctx);
+            }
+            TurnState::Paused => {
                 self.render_systems
-                    .execute(&mut self.ecs, &mut self.resources);
-                if self.frame_time > TURN_TIME {
-                    self.frame_time = 0.0;
-                    self.monster_systems
-                        .execute(&mut self.ecs, &mut self.resources);
-                }
+                    .execute(&mut self.game.ecs, &mut self.game.resources);
+                self.pause_menu(ctx);
             }
-            TurnState::GameOver => {
-                self.game_over(ctx);
+            TurnState::GamePlay
+            | TurnState::AwaitingInput
+            | TurnState::PlayerTurn
+            | TurnState::MonsterTurn
+            | TurnState::NextFloor
+            | TurnState::Targeting => {
+                self.render_systems
+                    .execute(&mut self.game.ecs, &mut self.game.resources);
             }
-            TurnState::Victory => {
-                self.victory(ctx);
+            TurnState::MessageHistory => {
+                self.message_history(ctx);
+            }
+            TurnState::Inventory => {
+                self.inventory(ctx);
+            }
+            TurnState::LevelUp => {
+                self.level_up();
             }
-            _ => {}
-        }
-
-        // self.realtime_systems
-        //     .execute(&mut self.ecs, &mut self.resources);
-        /*
-        match current_state {
-            TurnState::AwaitingInput => self
-                .input_systems
-                .execute(&mut self.ecs, &mut self.resources),
-            TurnState::PlayerTurn => {
-                self.player_systems
-                    .execute(&mut self.ecs, &mut self.resources);
-            }
-            TurnState::MonsterTurn => self
-                .monster_systems
-                .execute(&mut self.ecs, &mut self.resources),
             TurnState::GameOver => {
                 self.game_over(ctx);
             }
             TurnState::Victory => {
                 self.victory(ctx);
             }
-        }*/
+        }
 
         // -- Render Draw Buffer
         render_draw_buffer(ctx).expect("Render Draw Buffer ERROR");
@@ -203,6 +575,17 @@ impl GameState for State {
 }
 
 fn main() -> BError {
+    let args = cli::CommandLine::parse();
+
+    if let Some(script) = args.headless_script {
+        let mut headless = Headless::new(args.seed, args.mode, args.rules);
+        match headless.run_script(&script) {
+            Ok(_) => println!("{}", headless.summary()),
+            Err(e) => eprintln!("{e}"),
+        }
+        return Ok(());
+    }
+
     let context = BTermBuilder::new()
         .with_title("Mt. Septune")
         .with_fps_cap(60.0)
@@ -215,5 +598,5 @@ fn main() -> BError {
         .with_simple_console_no_bg(SCREEN_WIDTH, SCREEN_HEIGHT, "haro_16x16.png") // hud
         .build()?;
 
-    main_loop(context, State::new())
+    main_loop(context, State::title(args.seed, args.mode, args.rules))
 }
@@ -2,22 +2,52 @@ use crate::prelude::*;
 
 const NUM_TILES: usize = (SCREEN_WIDTH * SCREEN_HEIGHT) as usize;
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
     Floorboard,
     Grass,
     Ground,
-    Door,
+    Door(DoorState),
+    Stairs,
+}
+
+// Ordered by how hard a door is to get through, so that whoever can get
+// through a closed door can also get through an open one
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum DoorState {
+    Open,
+    Closed,
+    // only the player can get through, by forcing it
+    Locked,
 }
 
 pub fn map_idx(x: i32, y: i32) -> usize {
     ((y * SCREEN_WIDTH) + x) as usize
 }
 
+// The steps anything can take from where it stands: the four orthogonal ones
+// first, then the diagonals for runs that allow them
+pub const STEPS: [Point; 8] = [
+    Point { x: -1, y: 0 },
+    Point { x: 1, y: 0 },
+    Point { x: 0, y: -1 },
+    Point { x: 0, y: 1 },
+    Point { x: -1, y: -1 },
+    Point { x: 1, y: -1 },
+    Point { x: -1, y: 1 },
+    Point { x: 1, y: 1 },
+];
+
+#[derive(Serialize, Deserialize)]
 pub struct Map {
     pub tiles: Vec<TileType>,
     pub revealed_tiles: Vec<bool>,
+    // whether diagonal steps are allowed, as set by the run's rules
+    pub diagonals: bool,
+    // goes up every time a door opens or shuts
+    #[serde(default)]
+    revision: u64,
 }
 
 impl Map {
@@ -25,9 +55,24 @@ impl Map {
         Self {
             tiles: vec![TileType::Ground; NUM_TILES],
             revealed_tiles: vec![false; NUM_TILES],
+            diagonals: false,
+            revision: 0,
         }
     }
 
+    // How many times doors have opened or shut on this map, so that anything
+    // worked out from it can tell when it's out of date
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    // Opens, shuts or locks the door at `point`
+    pub fn set_door(&mut self, point: Point, state: DoorState) {
+        let idx = self.point2d_to_index(point);
+        self.tiles[idx] = TileType::Door(state);
+        self.revision += 1;
+    }
+
     pub fn in_bounds(&self, point: Point) -> bool {
         point.x >= 0 && point.x < SCREEN_WIDTH && point.y >= 0 && point.y < SCREEN_HEIGHT
     }
@@ -41,23 +86,95 @@ impl Map {
     }
 
     pub fn can_enter_tile(&self, point: Point) -> bool {
-        self.in_bounds(point) && self.tiles[map_idx(point.x, point.y)] != TileType::Wall
+        self.can_pass(point, DoorState::Open)
     }
 
-    fn valid_exit(&self, loc: Point, delta: Point) -> Option<usize> {
-        let destination = loc + delta;
+    // Whether someone who can get through doors up to `doors` can get onto
+    // `point`, opening a door on the way if need be
+    pub fn can_pass(&self, point: Point, doors: DoorState) -> bool {
+        match self.try_idx(point).map(|idx| self.tiles[idx]) {
+            None | Some(TileType::Wall) => false,
+            Some(TileType::Door(state)) => state <= doors,
+            Some(_) => true,
+        }
+    }
 
-        if self.in_bounds(destination) {
-            if self.can_enter_tile(destination) {
-                let idx = self.point2d_to_index(destination);
-                Some(idx)
-            } else {
-                None
-            }
+    // The state of the door at `point`, if there is one
+    pub fn door(&self, point: Point) -> Option<DoorState> {
+        match self.try_idx(point).map(|idx| self.tiles[idx]) {
+            Some(TileType::Door(state)) => Some(state),
+            _ => None,
+        }
+    }
+
+    // The map as seen by someone finding their way across it who can get
+    // through doors up to `doors`
+    pub fn pathing(&self, doors: DoorState) -> Pathing<'_> {
+        Pathing { map: self, doors }
+    }
+
+    // Whether a shot from `from` can reach `to` without passing through
+    // anything opaque on the way
+    pub fn has_line_of_fire(&self, from: Point, to: Point) -> bool {
+        line2d(LineAlg::Bresenham, from, to)
+            .iter()
+            .filter(|pos| **pos != from && **pos != to)
+            .all(|pos| self.in_bounds(*pos) && !self.is_opaque(self.point2d_to_index(*pos)))
+    }
+
+    // The steps allowed on this map
+    pub fn steps(&self) -> &'static [Point] {
+        if self.diagonals {
+            &STEPS
         } else {
-            None
+            &STEPS[..4]
         }
     }
+
+    // Whether a single step by `delta` from `from` is allowed. A diagonal step
+    // can't cut a corner: both tiles it squeezes between have to be open.
+    pub fn can_step(&self, from: Point, delta: Point) -> bool {
+        self.can_step_through(from, delta, DoorState::Open)
+    }
+
+    // Whether a step by `delta` from `from` is allowed for someone who can get
+    // through doors up to `doors`
+    pub fn can_step_through(&self, from: Point, delta: Point, doors: DoorState) -> bool {
+        if !self.can_pass(from + delta, doors) {
+            return false;
+        }
+        if delta.x == 0 || delta.y == 0 {
+            return true;
+        }
+        self.diagonals
+            && self.can_enter_tile(from + Point::new(delta.x, 0))
+            && self.can_enter_tile(from + Point::new(0, delta.y))
+    }
+
+    // Where a step from `idx` can lead, and what it costs. A diagonal step
+    // covers more ground, so paths only take one when it actually saves
+    // distance, and a shut door takes a turn to get open.
+    fn exits(&self, idx: usize, doors: DoorState) -> SmallVec<[(usize, f32); 10]> {
+        let mut exits = SmallVec::new();
+        let location = self.index_to_point2d(idx);
+
+        for delta in self.steps() {
+            if self.can_step_through(location, *delta, doors) {
+                let destination = location + *delta;
+                let mut cost = if delta.x != 0 && delta.y != 0 {
+                    std::f32::consts::SQRT_2
+                } else {
+                    1.0
+                };
+                if !self.can_enter_tile(destination) {
+                    cost += 1.0;
+                }
+                exits.push((self.point2d_to_index(destination), cost));
+            }
+        }
+
+        exits
+    }
 }
 
 impl Algorithm2D for Map {
@@ -73,33 +190,144 @@ impl Algorithm2D for Map {
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
         if self.in_bounds(self.index_to_point2d(idx)) {
-            self.tiles[idx as usize] == TileType::Wall
+            match self.tiles[idx] {
+                TileType::Wall => true,
+                TileType::Door(state) => state != DoorState::Open,
+                _ => false,
+            }
         } else {
             false
         }
     }
 
     fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> {
-        let mut exits = SmallVec::new();
-        let location = self.index_to_point2d(idx);
+        self.exits(idx, DoorState::Open)
+    }
 
-        if let Some(idx) = self.valid_exit(location, Point::new(-1, 0)) {
-            exits.push((idx, 1.0))
-        }
-        if let Some(idx) = self.valid_exit(location, Point::new(1, 0)) {
-            exits.push((idx, 1.0))
-        }
-        if let Some(idx) = self.valid_exit(location, Point::new(0, -1)) {
-            exits.push((idx, 1.0))
-        }
-        if let Some(idx) = self.valid_exit(location, Point::new(0, 1)) {
-            exits.push((idx, 1.0))
-        }
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        DistanceAlg::Pythagoras.distance2d(self.index_to_point2d(idx1), self.index_to_point2d(idx2))
+    }
+}
 
-        exits
+pub struct Pathing<'a> {
+    map: &'a Map,
+    doors: DoorState,
+}
+
+impl BaseMap for Pathing<'_> {
+    fn is_opaque(&self, idx: usize) -> bool {
+        self.map.is_opaque(idx)
+    }
+
+    fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> {
+        self.map.exits(idx, self.doors)
     }
 
     fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
-        DistanceAlg::Pythagoras.distance2d(self.index_to_point2d(idx1), self.index_to_point2d(idx2))
+        self.map.get_pathing_distance(idx1, idx2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOOR: Point = Point { x: 5, y: 5 };
+
+    fn map_with_door(state: DoorState) -> Map {
+        let mut map = Map::new();
+        map.set_door(DOOR, state);
+        map
+    }
+
+    #[test]
+    fn doors_block_sight_unless_open() {
+        let idx = map_idx(DOOR.x, DOOR.y);
+        assert!(!map_with_door(DoorState::Open).is_opaque(idx));
+        assert!(map_with_door(DoorState::Closed).is_opaque(idx));
+        assert!(map_with_door(DoorState::Locked).is_opaque(idx));
+
+        let mut map = Map::new();
+        assert!(!map.is_opaque(idx));
+        map.tiles[idx] = TileType::Wall;
+        assert!(map.is_opaque(idx));
+    }
+
+    #[test]
+    fn doors_let_through_those_who_can_get_past_them() {
+        use DoorState::*;
+
+        // the door, what its would-be passer can get through, and whether
+        // they get onto it
+        let cases = [
+            (Open, Open, true),
+            (Open, Closed, true),
+            (Open, Locked, true),
+            (Closed, Open, false),
+            (Closed, Closed, true),
+            (Closed, Locked, true),
+            (Locked, Open, false),
+            (Locked, Closed, false),
+            (Locked, Locked, true),
+        ];
+        for (door, doors, passes) in cases {
+            assert_eq!(
+                map_with_door(door).can_pass(DOOR, doors),
+                passes,
+                "{door:?} door, getting through {doors:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn nobody_gets_through_walls_or_off_the_map() {
+        let mut map = Map::new();
+        map.tiles[map_idx(DOOR.x, DOOR.y)] = TileType::Wall;
+        assert!(!map.can_pass(DOOR, DoorState::Locked));
+        assert!(!map.can_pass(Point::new(-1, 0), DoorState::Locked));
+        assert!(!map.can_pass(Point::new(0, SCREEN_HEIGHT), DoorState::Locked));
+    }
+
+    #[test]
+    fn diagonal_steps_only_when_the_rules_allow() {
+        let mut map = Map::new();
+        let from = Point::new(5, 5);
+        assert!(!map.can_step(from, Point::new(1, 1)));
+        assert!(map.can_step(from, Point::new(1, 0)));
+
+        map.diagonals = true;
+        assert!(map.can_step(from, Point::new(1, 1)));
+        assert!(map.can_step(from, Point::new(-1, -1)));
+    }
+
+    #[test]
+    fn diagonal_steps_cannot_cut_corners() {
+        let mut map = Map::new();
+        map.diagonals = true;
+        let from = Point::new(5, 5);
+        let delta = Point::new(1, 1);
+
+        // either tile squeezed between blocks the step, whichever side it's on
+        for corner in [Point::new(6, 5), Point::new(5, 6)] {
+            let mut walled = Map::new();
+            walled.diagonals = true;
+            walled.tiles[map_idx(corner.x, corner.y)] = TileType::Wall;
+            assert!(!walled.can_step(from, delta), "{:?}", corner);
+
+            walled.set_door(corner, DoorState::Closed);
+            assert!(!walled.can_step(from, delta), "{:?}", corner);
+            assert!(
+                !walled.can_step_through(from, delta, DoorState::Locked),
+                "{:?}",
+                corner
+            );
+
+            walled.set_door(corner, DoorState::Open);
+            assert!(walled.can_step(from, delta), "{:?}", corner);
+        }
+
+        // and nobody steps diagonally onto a wall either
+        map.tiles[map_idx(6, 6)] = TileType::Wall;
+        assert!(!map.can_step(from, delta));
     }
 }
@@ -12,6 +12,7 @@ impl MapArchitect for CellularAutomataArchitect {
             player_start: Point::zero(),
             egg_start: Point::zero(),
             theme: super::themes::DungeonTheme::new(),
+            architect: ArchitectKind::CellularAutomata,
         };
 
         self.random_noise_map(rng, &mut mb.map);
@@ -42,8 +43,8 @@ impl MapArchitect for CellularAutomataArchitect {
         let dijkstra_map = DijkstraMap::new(
             SCREEN_WIDTH,
             SCREEN_HEIGHT,
-            &vec![mb.map.point2d_to_index(player_start)],
-            &mb.map,
+            &[mb.map.point2d_to_index(player_start)],
+            &mb.map.pathing(DoorState::Locked),
             1024.0,
         );
         dijkstra_map
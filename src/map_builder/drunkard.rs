@@ -17,6 +17,7 @@ impl MapArchitect for DrunkardsWalkArchitect {
             player_start: Point::zero(),
             egg_start: Point::zero(),
             theme: super::themes::DungeonTheme::new(),
+            architect: ArchitectKind::DrunkardsWalk,
         };
         mb.fill(TileType::Wall);
         let center = Point::new(SCREEN_WIDTH / 2, SCREEN_HEIGHT / 2);
@@ -40,8 +41,8 @@ impl MapArchitect for DrunkardsWalkArchitect {
             let dijkstra_map = DijkstraMap::new(
                 SCREEN_WIDTH,
                 SCREEN_HEIGHT,
-                &vec![mb.map.point2d_to_index(center)],
-                &mb.map,
+                &[mb.map.point2d_to_index(center)],
+                &mb.map.pathing(DoorState::Locked),
                 1024.0,
             );
             dijkstra_map
@@ -12,6 +12,7 @@ impl MapArchitect for DungeonRoomsArchitect {
             player_start: Point::zero(),
             egg_start: Point::zero(),
             theme: super::themes::DungeonTheme::new(),
+            architect: ArchitectKind::DungeonRooms,
         };
 
         mb.fill(TileType::Wall);
@@ -12,6 +12,7 @@ impl MapArchitect for EmptyArchitect {
             player_start: Point::zero(),
             egg_start: Point::zero(),
             theme: super::themes::DungeonTheme::new(),
+            architect: ArchitectKind::Empty,
         };
 
         mb.fill(TileType::Ground);
@@ -14,6 +14,7 @@ impl MapArchitect for LabyruaryArchitect {
             player_start: Point::zero(),
             egg_start: Point::zero(),
             theme: super::themes::DungeonTheme::new(),
+            architect: ArchitectKind::Labyruary,
         };
 
         mb.fill(TileType::Grass);
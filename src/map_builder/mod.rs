@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use std::convert::TryFrom;
 
 mod automata;
 mod drunkard;
@@ -18,6 +19,62 @@ use themes::*;
 
 pub trait MapTheme: Sync + Send {
     fn tile_to_render(&self, tile_type: TileType) -> (FontCharType, ColorPair);
+    fn kind(&self) -> ThemeKind;
+}
+
+// Names a theme so it can be written to a save file and rebuilt on load
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ThemeKind {
+    Dungeon,
+    Forest,
+}
+
+impl ThemeKind {
+    pub const ALL: [ThemeKind; 2] = [ThemeKind::Dungeon, ThemeKind::Forest];
+
+    pub fn theme(self) -> Box<dyn MapTheme> {
+        match self {
+            ThemeKind::Dungeon => DungeonTheme::new(),
+            ThemeKind::Forest => ForestTheme::new(),
+        }
+    }
+}
+
+// Names the architect a floor was built by, for the record of the run
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ArchitectKind {
+    DungeonRooms,
+    Labyruary,
+    DrunkardsWalk,
+    CellularAutomata,
+    Empty,
+}
+
+impl ArchitectKind {
+    pub const ALL: [ArchitectKind; 5] = [
+        ArchitectKind::DungeonRooms,
+        ArchitectKind::Labyruary,
+        ArchitectKind::DrunkardsWalk,
+        ArchitectKind::CellularAutomata,
+        ArchitectKind::Empty,
+    ];
+
+    fn architect(self) -> Box<dyn MapArchitect> {
+        match self {
+            ArchitectKind::DungeonRooms => Box::new(DungeonRoomsArchitect {}),
+            ArchitectKind::Labyruary => Box::new(LabyruaryArchitect {}),
+            ArchitectKind::DrunkardsWalk => Box::new(DrunkardsWalkArchitect {}),
+            ArchitectKind::CellularAutomata => Box::new(CellularAutomataArchitect {}),
+            ArchitectKind::Empty => Box::new(EmptyArchitect {}),
+        }
+    }
+}
+
+// A practice run has every floor built by the same architect in the same theme
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Practice {
+    pub architect: ArchitectKind,
+    pub theme: ThemeKind,
 }
 
 trait MapArchitect {
@@ -30,28 +87,41 @@ pub struct MapBuilder {
     pub rooms: Vec<Rect>,
     pub monster_spawns: Vec<Point>,
     pub player_start: Point,
+    // The farthest reachable point from the start: stairs up on most floors,
+    // the egg on the last one
     pub egg_start: Point,
     pub theme: Box<dyn MapTheme>,
+    pub architect: ArchitectKind,
 }
 
 impl MapBuilder {
-    pub fn new(rng: &mut RandomNumberGenerator) -> Self {
-        let mut architect: Box<dyn MapArchitect> = match rng.range(0, 100) {
-            0..=20 => Box::new(DrunkardsWalkArchitect {}),
-            21..=45 => Box::new(DungeonRoomsArchitect {}),
-            46..=70 => Box::new(LabyruaryArchitect {}),
-            _ => Box::new(CellularAutomataArchitect {}),
+    pub fn new(rng: &mut RandomNumberGenerator, floor: &Floor, practice: Option<Practice>) -> Self {
+        // Built-up rooms and halls at the foot of the mountain give way to
+        // open caves further up
+        let architect = if let Some(practice) = practice {
+            practice.architect
+        } else {
+            let roll = rng.range(0, 100) + i32::try_from(floor.0 * 15).unwrap_or(i32::MAX);
+            match roll {
+                0..=30 => ArchitectKind::DungeonRooms,
+                31..=55 => ArchitectKind::Labyruary,
+                56..=80 => ArchitectKind::DrunkardsWalk,
+                _ => ArchitectKind::CellularAutomata,
+            }
         };
 
-        let mut mb = architect.new(rng);
+        let mut mb = architect.architect().new(rng);
         apply_prefab(&mut mb, rng);
 
         // So now we create new themes in the constructors of the mb:s,
         // consider a better solution since we're essentially doing it twice
         // (not that it matters for performance really)
-        mb.theme = match rng.range(0, 2) {
-            0 => DungeonTheme::new(),
-            _ => ForestTheme::new(),
+        mb.theme = match practice {
+            Some(practice) => practice.theme.theme(),
+            None => match rng.range(0, 2) {
+                0 => DungeonTheme::new(),
+                _ => ForestTheme::new(),
+            },
         };
 
         mb
@@ -89,8 +159,9 @@ impl MapBuilder {
         let dijkstra_map = DijkstraMap::new(
             SCREEN_WIDTH,
             SCREEN_HEIGHT,
-            &vec![self.map.point2d_to_index(start_point)],
-            &self.map,
+            &[self.map.point2d_to_index(start_point)],
+            // as far as the player can get, doors and all
+            &self.map.pathing(DoorState::Locked),
             1024.0,
         );
 
@@ -101,6 +172,7 @@ impl MapBuilder {
                 .iter()
                 .enumerate()
                 .filter(|(_, dist)| *dist < UNREACHABLE)
+                .filter(|(idx, _)| self.map.door(self.map.index_to_point2d(*idx)).is_none())
                 .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
                 .unwrap()
                 .0,
@@ -204,7 +276,13 @@ impl MapBuilder {
 
                 let door_idx = map_idx(door_x, door_y);
 
-                self.map.tiles[door_idx] = TileType::Door;
+                // most doors are shut, a few are left open and a few locked
+                let state = match rng.range(0, 10) {
+                    0 => DoorState::Locked,
+                    1 | 2 => DoorState::Open,
+                    _ => DoorState::Closed,
+                };
+                self.map.tiles[door_idx] = TileType::Door(state);
             }
         }
     }
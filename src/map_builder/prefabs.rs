@@ -24,8 +24,8 @@ pub fn apply_prefab(mb: &mut MapBuilder, rng: &mut RandomNumberGenerator) {
     let dijkstra_map = DijkstraMap::new(
         SCREEN_WIDTH,
         SCREEN_HEIGHT,
-        &vec![mb.map.point2d_to_index(mb.player_start)],
-        &mb.map,
+        &[mb.map.point2d_to_index(mb.player_start)],
+        &mb.map.pathing(DoorState::Locked),
         1024.0,
     );
 
@@ -27,12 +27,24 @@ impl MapTheme for DungeonTheme {
                 to_cp437('"'),
                 ColorPair::new(RGB::from_u8(2, 219, 158), RGB::from_u8(2, 168, 129)),
             ),
-            TileType::Door => (
+            TileType::Door(DoorState::Open) => (
+                to_cp437('\''),
+                ColorPair::new(RGB::from_u8(214, 110, 105), RGB::from_u8(150, 100, 72)),
+            ),
+            TileType::Door(_) => (
                 to_cp437('▬'),
                 ColorPair::new(RGB::from_u8(214, 110, 105), RGB::from_u8(150, 100, 72)),
             ),
+            TileType::Stairs => (
+                to_cp437('<'),
+                ColorPair::new(RGB::from_u8(255, 204, 51), RGB::from_u8(11, 11, 15)),
+            ),
         }
     }
+
+    fn kind(&self) -> ThemeKind {
+        ThemeKind::Dungeon
+    }
 }
 
 pub struct ForestTheme {}
@@ -56,12 +68,24 @@ impl MapTheme for ForestTheme {
                 to_cp437('`'),
                 ColorPair::new(RGB::from_u8(0, 140, 105), RGB::from_u8(0, 64, 64)),
             ),
-            TileType::Door => (
+            TileType::Door(DoorState::Open) => (
+                to_cp437('\''),
+                ColorPair::new(RGB::from_u8(128, 128, 128), RGB::from_u8(0, 64, 64)),
+            ),
+            TileType::Door(_) => (
                 to_cp437('∩'),
                 ColorPair::new(RGB::from_u8(128, 128, 128), RGB::from_u8(0, 64, 64)),
             ),
+            TileType::Stairs => (
+                to_cp437('<'),
+                ColorPair::new(RGB::from_u8(255, 204, 51), RGB::from_u8(0, 64, 64)),
+            ),
         }
     }
+
+    fn kind(&self) -> ThemeKind {
+        ThemeKind::Forest
+    }
 }
 
 impl ForestTheme {
@@ -0,0 +1,352 @@
+use crate::prelude::*;
+use crate::State;
+use std::path::Path;
+
+const MAIN_MENU: [&str; 6] = [
+    "New game",
+    "Continue",
+    "Enter a seed",
+    "Practice run",
+    "Options",
+    "Quit",
+];
+const PAUSE_MENU: [&str; 4] = ["Resume", "Options", "Save and quit", "Abandon run"];
+const PRACTICE_MENU: [&str; 3] = ["Architect", "Theme", "Start"];
+const OPTIONS_MENU: [&str; 2] = ["Mode", "Diagonal movement"];
+// Long enough for any u64
+const MAX_SEED_DIGITS: usize = 20;
+
+impl State {
+    // Switches to another screen, with its cursor back at the top
+    pub fn open(&mut self, screen: TurnState) {
+        self.menu_cursor = 0;
+        self.menu_message = None;
+        self.game.resources.insert(screen);
+    }
+
+    pub fn main_menu(&mut self, ctx: &mut BTerm) {
+        let has_save = Path::new(SAVE_PATH).exists();
+
+        let mut draw_batch = DrawBatch::new();
+        draw_batch.target(2);
+        draw_batch.print_color_centered(10, "MT. SEPTUNE", ColorPair::new(GOLD, BLACK));
+        draw_batch.print_color_centered(
+            12,
+            "Somewhere up there lies the Cosmic Egg.",
+            ColorPair::new(WHITE, BLACK),
+        );
+        let enabled = |i: usize| i != 1 || has_save;
+        draw_menu(&mut draw_batch, 16, &MAIN_MENU, self.menu_cursor, enabled);
+        let help = format!(
+            "{} - {} to choose",
+            self.select_help(),
+            self.key_name(Action::Select)
+        );
+        self.draw_footer(&mut draw_batch, &help);
+        draw_batch.submit(20000).expect("Main Menu Batch Error");
+
+        match self.pick(ctx.key, MAIN_MENU.len()) {
+            Some(0) => {
+                let seed = self.game.resources.get::<Seed>().unwrap().clone();
+                *self = State::new(seed, self.mode, self.rules);
+            }
+            Some(1) => self.continue_run(),
+            Some(2) => {
+                self.seed_entry.clear();
+                self.open(TurnState::SeedEntry);
+            }
+            Some(3) => self.open(TurnState::PracticeMenu),
+            Some(4) => {
+                self.options_from = TurnState::MainMenu;
+                self.open(TurnState::Options);
+            }
+            Some(5) => ctx.quit(),
+            _ => {}
+        }
+    }
+
+    // Picks up the saved run. The save is removed once restored, so a run can
+    // only be continued from where it was last left.
+    fn continue_run(&mut self) {
+        let path = Path::new(SAVE_PATH);
+        if !path.exists() {
+            self.menu_message = Some("There is no saved run.".to_string());
+            return;
+        }
+
+        match load_game(path) {
+            Ok(game) => {
+                if let Err(e) = std::fs::remove_file(path) {
+                    eprintln!("could not remove {}: {e}", path.display());
+                }
+                *self = State::from_game(game);
+            }
+            Err(e) => self.menu_message = Some(capitalize(&e)),
+        }
+    }
+
+    pub fn seed_menu(&mut self, ctx: &mut BTerm) {
+        let select = self.menu_action(ctx.key) == Some(Action::Select);
+        match ctx.key {
+            Some(VirtualKeyCode::Escape) => {
+                self.open(TurnState::MainMenu);
+                return;
+            }
+            Some(VirtualKeyCode::Back) => {
+                self.seed_entry.pop();
+            }
+            Some(_) if select => match Seed::parse(&self.seed_entry) {
+                Some(seed) => {
+                    *self = State::new(seed, self.mode, self.rules);
+                    return;
+                }
+                None => self.menu_message = Some("That is not a seed.".to_string()),
+            },
+            Some(key) => {
+                if let Some(digit) = digit(key) {
+                    if self.seed_entry.len() < MAX_SEED_DIGITS {
+                        self.seed_entry.push(digit);
+                        self.menu_message = None;
+                    }
+                }
+            }
+            None => {}
+        }
+
+        let mut draw_batch = DrawBatch::new();
+        draw_batch.target(2);
+        draw_batch.print_color_centered(10, "ENTER A SEED", ColorPair::new(GOLD, BLACK));
+        draw_batch.print_color_centered(
+            14,
+            format!("{}_", self.seed_entry),
+            ColorPair::new(WHITE, BLACK),
+        );
+        let help = format!(
+            "Type the seed - {} to start - ESC to return",
+            self.key_name(Action::Select)
+        );
+        self.draw_footer(&mut draw_batch, &help);
+        draw_batch.submit(20000).expect("Seed Entry Batch Error");
+    }
+
+    // Every floor of a practice run is built by the architect and in the
+    // theme picked here
+    pub fn practice_menu(&mut self, ctx: &mut BTerm) {
+        if ctx.key == Some(VirtualKeyCode::Escape) {
+            self.open(TurnState::MainMenu);
+            return;
+        }
+
+        let step = match self.menu_action(ctx.key) {
+            Some(Action::MenuLeft) => -1,
+            Some(Action::MenuRight) => 1,
+            _ => 0,
+        };
+        match self.menu_cursor {
+            0 => {
+                self.practice.architect = cycle(&ArchitectKind::ALL, self.practice.architect, step);
+            }
+            1 => self.practice.theme = cycle(&ThemeKind::ALL, self.practice.theme, step),
+            _ => {}
+        }
+
+        let labels = [
+            format!("Architect: < {:?} >", self.practice.architect),
+            format!("Theme: < {:?} >", self.practice.theme),
+            PRACTICE_MENU[2].to_string(),
+        ];
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+
+        let mut draw_batch = DrawBatch::new();
+        draw_batch.target(2);
+        draw_batch.print_color_centered(10, "PRACTICE RUN", ColorPair::new(GOLD, BLACK));
+        draw_batch.print_color_centered(
+            12,
+            "Every floor is built the same way. Practice runs earn no high scores.",
+            ColorPair::new(WHITE, BLACK),
+        );
+        draw_menu(&mut draw_batch, 16, &labels, self.menu_cursor, |_| true);
+        let help = format!(
+            "{} - {}/{} to change - {} to start - ESC to return",
+            self.select_help(),
+            self.key_name(Action::MenuLeft),
+            self.key_name(Action::MenuRight),
+            self.key_name(Action::Select)
+        );
+        self.draw_footer(&mut draw_batch, &help);
+        draw_batch.submit(20000).expect("Practice Menu Batch Error");
+
+        if self.pick(ctx.key, PRACTICE_MENU.len()).is_some() {
+            let seed = self.game.resources.get::<Seed>().unwrap().clone();
+            *self = State::from_game(Game::practice(seed, self.mode, self.rules, self.practice));
+        }
+    }
+
+    // Settings for the runs to come; a run keeps the ones it was started with
+    pub fn options(&mut self, ctx: &mut BTerm) {
+        if ctx.key == Some(VirtualKeyCode::Escape) {
+            let back = self.options_from;
+            self.open(back);
+            return;
+        }
+        if let Some(Action::MenuLeft | Action::MenuRight | Action::Select) =
+            self.menu_action(ctx.key)
+        {
+            match self.menu_cursor {
+                0 => {
+                    self.mode = match self.mode {
+                        GameMode::RealTime => GameMode::TurnBased,
+                        GameMode::TurnBased => GameMode::RealTime,
+                    };
+                }
+                _ => self.rules.diagonals = !self.rules.diagonals,
+            }
+        }
+        self.pick(ctx.key, OPTIONS_MENU.len());
+
+        let mode = match self.mode {
+            GameMode::RealTime => "Real-time",
+            GameMode::TurnBased => "Turn-based",
+        };
+        let diagonals = if self.rules.diagonals { "On" } else { "Off" };
+        let labels = [
+            format!("{}: < {mode} >", OPTIONS_MENU[0]),
+            format!("{}: < {diagonals} >", OPTIONS_MENU[1]),
+        ];
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+
+        let mut draw_batch = DrawBatch::new();
+        draw_batch.target(2);
+        draw_batch.print_color_centered(10, "OPTIONS", ColorPair::new(GOLD, BLACK));
+        draw_menu(&mut draw_batch, 16, &labels, self.menu_cursor, |_| true);
+        draw_batch.print_color_centered(
+            21,
+            "Takes effect from the next run.",
+            ColorPair::new(GRAY, BLACK),
+        );
+        let help = format!(
+            "{} - {}/{} to change - ESC to return",
+            self.select_help(),
+            self.key_name(Action::MenuLeft),
+            self.key_name(Action::MenuRight)
+        );
+        self.draw_footer(&mut draw_batch, &help);
+        draw_batch.submit(20000).expect("Options Batch Error");
+    }
+
+    // Drawn over the frozen game. Time stands still until play resumes.
+    pub fn pause_menu(&mut self, ctx: &mut BTerm) {
+        if ctx.key == Some(VirtualKeyCode::Escape) {
+            self.open(self.game.mode.starting_state());
+            return;
+        }
+
+        let mut draw_batch = DrawBatch::new();
+        draw_batch.target(2);
+        draw_batch.print_color_centered(14, "PAUSED", ColorPair::new(GOLD, BLACK));
+        draw_menu(&mut draw_batch, 17, &PAUSE_MENU, self.menu_cursor, |_| true);
+        let help = format!(
+            "{} - {} to choose - ESC to resume",
+            self.select_help(),
+            self.key_name(Action::Select)
+        );
+        self.draw_footer(&mut draw_batch, &help);
+        draw_batch.submit(20000).expect("Pause Menu Batch Error");
+
+        match self.pick(ctx.key, PAUSE_MENU.len()) {
+            Some(0) => self.open(self.game.mode.starting_state()),
+            Some(1) => {
+                self.options_from = TurnState::Paused;
+                self.open(TurnState::Options);
+            }
+            Some(2) => {
+                // should saving fail, the reason ends up in the message log
+                self.open(self.game.mode.starting_state());
+                self.save_and_quit(ctx);
+            }
+            Some(3) => *self = State::title(Seed::random(), self.mode, self.rules),
+            _ => {}
+        }
+    }
+
+    // Moves the cursor through a menu of `len` entries, returning the entry
+    // picked with Select
+    fn pick(&mut self, key: Option<VirtualKeyCode>, len: usize) -> Option<usize> {
+        match self.menu_action(key) {
+            Some(Action::MenuUp) => {
+                self.menu_cursor = (self.menu_cursor + len - 1) % len;
+                None
+            }
+            Some(Action::MenuDown) => {
+                self.menu_cursor = (self.menu_cursor + 1) % len;
+                None
+            }
+            Some(Action::Select) => Some(self.menu_cursor),
+            _ => None,
+        }
+    }
+
+    // How to move through a menu, as the first part of its help line
+    fn select_help(&self) -> String {
+        format!(
+            "{}/{} to select",
+            self.key_name(Action::MenuUp),
+            self.key_name(Action::MenuDown)
+        )
+    }
+
+    fn draw_footer(&self, draw_batch: &mut DrawBatch, help: &str) {
+        if let Some(message) = &self.menu_message {
+            draw_batch.print_color_centered(SCREEN_HEIGHT - 6, message, ColorPair::new(RED, BLACK));
+        }
+        draw_batch.print_color_centered(SCREEN_HEIGHT - 4, help, ColorPair::new(GREEN, BLACK));
+    }
+}
+
+// One entry a line from `top` down, the selected one highlighted and the ones
+// not on offer grayed out
+fn draw_menu(
+    draw_batch: &mut DrawBatch,
+    top: i32,
+    entries: &[&str],
+    cursor: usize,
+    enabled: impl Fn(usize) -> bool,
+) {
+    for (y, (i, entry)) in (top..).step_by(2).zip(entries.iter().enumerate()) {
+        let (text, color) = match (i == cursor, enabled(i)) {
+            (true, true) => (format!("> {entry} <"), GOLD),
+            (true, false) => (format!("> {entry} <"), GRAY),
+            (false, true) => ((*entry).to_string(), WHITE),
+            (false, false) => ((*entry).to_string(), GRAY),
+        };
+        draw_batch.print_color_centered(y, text, ColorPair::new(color, BLACK));
+    }
+}
+
+// The entry `step` places along from `current`, wrapping around at either end
+fn cycle<T: Copy + PartialEq>(all: &[T], current: T, step: i32) -> T {
+    let len = all.len();
+    let at = all.iter().position(|kind| *kind == current).unwrap_or(0);
+    let next = match step {
+        1 => (at + 1) % len,
+        -1 => (at + len - 1) % len,
+        _ => at,
+    };
+    all[next]
+}
+
+fn digit(key: VirtualKeyCode) -> Option<char> {
+    match key {
+        VirtualKeyCode::Key0 | VirtualKeyCode::Numpad0 => Some('0'),
+        VirtualKeyCode::Key1 | VirtualKeyCode::Numpad1 => Some('1'),
+        VirtualKeyCode::Key2 | VirtualKeyCode::Numpad2 => Some('2'),
+        VirtualKeyCode::Key3 | VirtualKeyCode::Numpad3 => Some('3'),
+        VirtualKeyCode::Key4 | VirtualKeyCode::Numpad4 => Some('4'),
+        VirtualKeyCode::Key5 | VirtualKeyCode::Numpad5 => Some('5'),
+        VirtualKeyCode::Key6 | VirtualKeyCode::Numpad6 => Some('6'),
+        VirtualKeyCode::Key7 | VirtualKeyCode::Numpad7 => Some('7'),
+        VirtualKeyCode::Key8 | VirtualKeyCode::Numpad8 => Some('8'),
+        VirtualKeyCode::Key9 | VirtualKeyCode::Numpad9 => Some('9'),
+        _ => None,
+    }
+}
@@ -0,0 +1,72 @@
+use crate::prelude::*;
+use std::convert::TryFrom;
+
+// A line in the log, built up out of differently colored pieces of text
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub segments: Vec<(String, RGB)>,
+}
+
+impl Message {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text<S: Into<String>>(self, text: S) -> Self {
+        self.colored(text, RGB::named(WHITE))
+    }
+
+    pub fn colored<S: Into<String>, C: Into<RGB>>(mut self, text: S, color: C) -> Self {
+        self.segments.push((text.into(), color.into()));
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub turn: u64,
+    pub message: Message,
+}
+
+// Everything that has happened so far in the run, oldest first
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageLog {
+    pub entries: Vec<LogEntry>,
+}
+
+impl MessageLog {
+    pub fn add(&mut self, clock: &GameClock, message: Message) {
+        self.entries.push(LogEntry {
+            turn: clock.turn(),
+            message,
+        });
+    }
+
+    pub fn latest(&self, count: usize) -> &[LogEntry] {
+        &self.entries[self.entries.len().saturating_sub(count)..]
+    }
+}
+
+pub fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// Draws an entry starting at `pos`, prefixed by the turn it happened on
+pub fn draw_log_entry(draw_batch: &mut DrawBatch, pos: Point, entry: &LogEntry) {
+    let stamp = format!("{:>5} ", entry.turn);
+    draw_batch.print_color(pos, &stamp, ColorPair::new(GRAY, BLACK));
+
+    let mut x = pos.x + text_width(&stamp);
+    for (text, color) in &entry.message.segments {
+        draw_batch.print_color(Point::new(x, pos.y), text, ColorPair::new(*color, BLACK));
+        x += text_width(text);
+    }
+}
+
+fn text_width(text: &str) -> i32 {
+    i32::try_from(text.chars().count()).unwrap_or(i32::MAX)
+}
@@ -0,0 +1,341 @@
+use crate::prelude::*;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+// Every finished run is appended to the morgue file, and the best ones are
+// kept in the high score table
+pub const MORGUE_PATH: &str = "morgue.txt";
+pub const HIGH_SCORES_PATH: &str = "highscores.json";
+const HIGH_SCORES_KEPT: usize = 10;
+
+// What a run scores: every floor reached, monster killed and level gained, and
+// a bonus for bringing the egg home
+const SCORE_PER_FLOOR: i32 = 100;
+const SCORE_PER_KILL: i32 = 10;
+const SCORE_PER_LEVEL: i32 = 25;
+const SCORE_FOR_VICTORY: i32 = 1000;
+
+// Everything worth remembering about a run once it's over
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunSummary {
+    pub seed: u64,
+    pub mode: GameMode,
+    pub practice: Option<Practice>,
+    pub victory: bool,
+    pub cause: String,
+    // counting from 1 at the foot, like the floors in the template file
+    pub floor: u32,
+    pub turns: u64,
+    pub level: i32,
+    pub layouts: Vec<(ArchitectKind, ThemeKind)>,
+    pub kills: BTreeMap<String, i32>,
+    pub items_used: BTreeMap<String, i32>,
+}
+
+impl RunSummary {
+    pub fn new(game: &Game) -> Self {
+        let resources = &game.resources;
+        let victory = game.turn_state() == TurnState::Victory;
+        let floor = resources.get::<Floor>().unwrap().0 + 1;
+
+        let player = <Entity>::query()
+            .filter(component::<Player>())
+            .iter(&game.ecs)
+            .copied()
+            .next()
+            .unwrap();
+        let entry = game.ecs.entry_ref(player).unwrap();
+
+        let cause = if victory {
+            "Brought the Cosmic Egg home".to_string()
+        } else {
+            format!("{} on floor {floor}", cause_of_death(&game.ecs, player))
+        };
+
+        Self {
+            seed: resources.get::<Seed>().unwrap().0,
+            mode: game.mode,
+            practice: resources.get::<Practice>().map(|practice| *practice),
+            victory,
+            cause,
+            floor,
+            turns: resources.get::<GameClock>().unwrap().turn(),
+            level: entry.get_component::<Experience>().map_or(1, |xp| xp.level),
+            layouts: resources.get::<Layouts>().unwrap().0.clone(),
+            kills: entry
+                .get_component::<Kills>()
+                .map(|kills| kills.0.clone())
+                .unwrap_or_default(),
+            items_used: entry
+                .get_component::<ItemsUsed>()
+                .map(|used| used.0.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn score(&self) -> i32 {
+        let floors = i32::try_from(self.floor).unwrap_or(i32::MAX);
+        let kills: i32 = self.kills.values().sum();
+        let victory = if self.victory { SCORE_FOR_VICTORY } else { 0 };
+        floors * SCORE_PER_FLOOR
+            + kills * SCORE_PER_KILL
+            + (self.level - 1) * SCORE_PER_LEVEL
+            + victory
+    }
+
+    // The run written out for the morgue file
+    pub fn morgue_entry(&self) -> String {
+        let mut lines = vec![
+            format!(
+                "=== Seed {} ({:?}{}) ===",
+                self.seed,
+                self.mode,
+                if self.practice.is_some() { ", practice" } else { "" }
+            ),
+            format!(
+                "{} after {} turns, at level {}.",
+                self.cause, self.turns, self.level
+            ),
+            format!("Score: {}", self.score()),
+            String::new(),
+            "Floors:".to_string(),
+        ];
+        for (n, (architect, theme)) in (1..).zip(&self.layouts) {
+            lines.push(format!("  {n}: {architect:?} architect, {theme:?} theme"));
+        }
+        lines.push("Kills:".to_string());
+        lines.extend(tally(&self.kills));
+        lines.push("Items used:".to_string());
+        lines.extend(tally(&self.items_used));
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+fn tally(counts: &BTreeMap<String, i32>) -> Vec<String> {
+    if counts.is_empty() {
+        return vec!["  none".to_string()];
+    }
+    counts
+        .iter()
+        .map(|(name, count)| format!("  {name} x{count}"))
+        .collect()
+}
+
+// Whoever dealt the player the final blow, as the death system left it
+fn cause_of_death(ecs: &World, player: Entity) -> String {
+    let dead = ecs
+        .entry_ref(player)
+        .ok()
+        .and_then(|entry| entry.get_component::<Dead>().ok().copied());
+    let poisoner = ecs
+        .entry_ref(player)
+        .ok()
+        .and_then(|entry| entry.get_component::<Poisoned>().ok().copied())
+        .map(|poisoned| poisoned.by);
+
+    match dead {
+        Some(Dead { killer }) if poisoner == Some(killer) => "Succumbed to poison".to_string(),
+        Some(Dead { killer }) if killer == player => "Died by their own hand".to_string(),
+        Some(Dead { killer }) => ecs
+            .entry_ref(killer)
+            .ok()
+            .and_then(|entry| {
+                entry
+                    .get_component::<Name>()
+                    .ok()
+                    .map(|name| name.0.clone())
+            })
+            .map_or("Died".to_string(), |name| {
+                let article = if name.starts_with(['A', 'E', 'I', 'O', 'U']) {
+                    "an"
+                } else {
+                    "a"
+                };
+                format!("Killed by {article} {name}")
+            }),
+        None => "Died".to_string(),
+    }
+}
+
+pub fn write_morgue(summary: &RunSummary, path: &Path) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("could not open {}: {e}", path.display()))?;
+    writeln!(file, "{}", summary.morgue_entry())
+        .map_err(|e| format!("could not write {}: {e}", path.display()))
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub score: i32,
+    pub seed: u64,
+    pub cause: String,
+    pub turns: u64,
+}
+
+// The best runs so far, highest score first
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HighScores(pub Vec<HighScore>);
+
+impl HighScores {
+    // The table kept at `path`, empty if there is none yet. One that is there
+    // but can't be read is an error, so that it never gets written over.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("could not read {}: {e}", path.display())),
+        };
+        serde_json::from_str(&text).map_err(|e| {
+            format!(
+                "{} is not a high score table, so it was left alone: {e}",
+                path.display()
+            )
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("could not save scores: {e}"))?;
+        fs::write(path, text).map_err(|e| format!("could not write {}: {e}", path.display()))
+    }
+
+    // Enters a run into the table, returning its place if it made the cut.
+    // Ties go to the earlier run.
+    pub fn insert(&mut self, summary: &RunSummary) -> Option<usize> {
+        let score = summary.score();
+        let place = self
+            .0
+            .iter()
+            .take_while(|entry| entry.score >= score)
+            .count();
+        if place >= HIGH_SCORES_KEPT {
+            return None;
+        }
+        self.0.insert(
+            place,
+            HighScore {
+                score,
+                seed: summary.seed,
+                cause: summary.cause.clone(),
+                turns: summary.turns,
+            },
+        );
+        self.0.truncate(HIGH_SCORES_KEPT);
+        Some(place)
+    }
+}
+
+// A finished run as the end screens show it
+pub struct RunRecord {
+    pub summary: RunSummary,
+    pub high_scores: HighScores,
+    pub place: Option<usize>,
+    // why the run could not be written down, if it couldn't
+    pub error: Option<String>,
+}
+
+impl RunRecord {
+    pub fn write(game: &Game) -> Self {
+        let summary = RunSummary::new(game);
+        let morgue = write_morgue(&summary, Path::new(MORGUE_PATH));
+        let (high_scores, place, scores) = match HighScores::load(Path::new(HIGH_SCORES_PATH)) {
+            Ok(mut high_scores) => {
+                // practice runs are there to try things out, not to compete
+                let place = if summary.practice.is_some() {
+                    None
+                } else {
+                    high_scores.insert(&summary)
+                };
+                let saved = high_scores.save(Path::new(HIGH_SCORES_PATH));
+                (high_scores, place, saved)
+            }
+            Err(e) => (HighScores::default(), None, Err(e)),
+        };
+        let error = morgue.and(scores).err();
+        Self {
+            summary,
+            high_scores,
+            place,
+            error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A run from `seed` that died on `floor`, scoring 100 a floor
+    fn run(seed: u64, floor: u32) -> RunSummary {
+        RunSummary {
+            seed,
+            mode: GameMode::RealTime,
+            practice: None,
+            victory: false,
+            cause: "Died".to_string(),
+            floor,
+            turns: 100,
+            level: 1,
+            layouts: Vec::new(),
+            kills: BTreeMap::new(),
+            items_used: BTreeMap::new(),
+        }
+    }
+
+    fn seeds(high_scores: &HighScores) -> Vec<u64> {
+        high_scores.0.iter().map(|entry| entry.seed).collect()
+    }
+
+    #[test]
+    fn scores_are_kept_highest_first() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(high_scores.insert(&run(1, 2)), Some(0));
+        assert_eq!(high_scores.insert(&run(2, 4)), Some(0));
+        assert_eq!(high_scores.insert(&run(3, 3)), Some(1));
+        assert_eq!(seeds(&high_scores), vec![2, 3, 1]);
+    }
+
+    #[test]
+    fn a_tie_goes_to_the_earlier_run() {
+        let mut high_scores = HighScores::default();
+        high_scores.insert(&run(1, 2));
+        assert_eq!(high_scores.insert(&run(2, 2)), Some(1));
+        assert_eq!(seeds(&high_scores), vec![1, 2]);
+    }
+
+    #[test]
+    fn only_the_best_runs_are_kept() {
+        let mut high_scores = HighScores::default();
+        for seed in 0..HIGH_SCORES_KEPT as u64 {
+            high_scores.insert(&run(seed, 3));
+        }
+        // a tie with the last place doesn't make the cut either
+        assert_eq!(high_scores.insert(&run(100, 3)), None);
+        assert_eq!(high_scores.insert(&run(101, 2)), None);
+        assert_eq!(high_scores.0.len(), HIGH_SCORES_KEPT);
+
+        assert_eq!(high_scores.insert(&run(102, 4)), Some(0));
+        assert_eq!(high_scores.0.len(), HIGH_SCORES_KEPT);
+        assert_eq!(high_scores.0[0].seed, 102);
+        assert!(!seeds(&high_scores).contains(&(HIGH_SCORES_KEPT as u64 - 1)));
+    }
+
+    #[test]
+    fn a_table_that_cannot_be_read_is_an_error() {
+        let path =
+            std::env::temp_dir().join(format!("mt_septune_scores_{}.json", std::process::id()));
+        assert_eq!(HighScores::load(&path), Ok(HighScores::default()));
+
+        fs::write(&path, "[{\"score\": ").unwrap();
+        let loaded = HighScores::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(loaded.is_err());
+    }
+}
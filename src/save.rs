@@ -0,0 +1,210 @@
+use crate::prelude::*;
+use serde::de::{DeserializeOwned, DeserializeSeed};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+// Bump whenever the layout of a save file or of a saved component changes.
+// Saves from any other version are refused rather than misread.
+pub const SAVE_VERSION: u64 = 8;
+pub const SAVE_PATH: &str = "savegame.json";
+
+// Every component that can end up on an entity. The keys are written to the
+// save file, so renaming one breaks older saves.
+fn registry() -> Registry<String> {
+    let mut registry = Registry::<String>::default();
+    registry.register::<()>("unit".to_string());
+    registry.register::<Point>("point".to_string());
+    registry.register::<Render>("render".to_string());
+    registry.register::<Player>("player".to_string());
+    registry.register::<Enemy>("enemy".to_string());
+    registry.register::<Item>("item".to_string());
+    registry.register::<ActivateItem>("activate_item".to_string());
+    registry.register::<Carried>("carried".to_string());
+    registry.register::<Equippable>("equippable".to_string());
+    registry.register::<Equipped>("equipped".to_string());
+    registry.register::<StatBonus>("stat_bonus".to_string());
+    registry.register::<Ranged>("ranged".to_string());
+    registry.register::<Charges>("charges".to_string());
+    registry.register::<Targeting>("targeting".to_string());
+    registry.register::<Travelling>("travelling".to_string());
+    registry.register::<CosmicEgg>("cosmic_egg".to_string());
+    registry.register::<MovingRandomly>("moving_randomly".to_string());
+    registry.register::<OpensDoors>("opens_doors".to_string());
+    registry.register::<Brain>("brain".to_string());
+    registry.register::<WantsToMove>("wants_to_move".to_string());
+    registry.register::<MoveBlocked>("move_blocked".to_string());
+    registry.register::<WantsToAttack>("wants_to_attack".to_string());
+    registry.register::<WantsToDrop>("wants_to_drop".to_string());
+    registry.register::<WantsToEquip>("wants_to_equip".to_string());
+    registry.register::<WantsToUnequip>("wants_to_unequip".to_string());
+    registry.register::<Health>("health".to_string());
+    registry.register::<Name>("name".to_string());
+    registry.register::<Description>("description".to_string());
+    registry.register::<Attack>("attack".to_string());
+    registry.register::<Defense>("defense".to_string());
+    registry.register::<Energy>("energy".to_string());
+    registry.register::<FieldOfView>("field_of_view".to_string());
+    registry.register::<ProvidesHealing>("provides_healing".to_string());
+    registry.register::<ProvidesDungeonMap>("provides_dungeon_map".to_string());
+    registry.register::<InflictsDamage>("inflicts_damage".to_string());
+    registry.register::<InflictsConfusion>("inflicts_confusion".to_string());
+    registry.register::<InflictsFear>("inflicts_fear".to_string());
+    registry.register::<InflictsPoison>("inflicts_poison".to_string());
+    registry.register::<InflictsSlow>("inflicts_slow".to_string());
+    registry.register::<ProvidesRegeneration>("provides_regeneration".to_string());
+    registry.register::<ProvidesTeleport>("provides_teleport".to_string());
+    registry.register::<ProvidesBlink>("provides_blink".to_string());
+    registry.register::<RevealsEntities>("reveals_entities".to_string());
+    registry.register::<AreaOfEffect>("area_of_effect".to_string());
+    registry.register::<Effect>("effect".to_string());
+    registry.register::<Confused>("confused".to_string());
+    registry.register::<Afraid>("afraid".to_string());
+    registry.register::<Poisoned>("poisoned".to_string());
+    registry.register::<Slowed>("slowed".to_string());
+    registry.register::<Regenerating>("regenerating".to_string());
+    registry.register::<Revealed>("revealed".to_string());
+    registry.register::<GrantsXp>("grants_xp".to_string());
+    registry.register::<Experience>("experience".to_string());
+    registry.register::<GainXp>("gain_xp".to_string());
+    registry.register::<Dead>("dead".to_string());
+    registry.register::<Corpse>("corpse".to_string());
+    registry.register::<OnDeath>("on_death".to_string());
+    registry.register::<Kills>("kills".to_string());
+    registry.register::<ItemsUsed>("items_used".to_string());
+    registry
+}
+
+pub fn save_game(game: &Game, path: &Path) -> Result<(), String> {
+    let resources = &game.resources;
+    let registry = registry();
+
+    let save = json!({
+        "version": SAVE_VERSION,
+        "mode": game.mode,
+        "rules": &*resources.get::<Rules>().unwrap(),
+        "seed": &*resources.get::<Seed>().unwrap(),
+        "map": &*resources.get::<Map>().unwrap(),
+        "camera": &*resources.get::<Camera>().unwrap(),
+        "theme": resources.get::<Box<dyn MapTheme>>().unwrap().kind(),
+        "rng": &*resources.get::<RandomNumberGenerator>().unwrap(),
+        "clock": &*resources.get::<GameClock>().unwrap(),
+        "floor": &*resources.get::<Floor>().unwrap(),
+        "layouts": &*resources.get::<Layouts>().unwrap(),
+        "practice": resources.get::<Practice>().map(|practice| *practice),
+        "log": &*resources.get::<MessageLog>().unwrap(),
+        "world": game.ecs.as_serializable(any(), &registry),
+    });
+
+    let text = serde_json::to_string(&save).map_err(|e| format!("could not save: {e}"))?;
+    fs::write(path, text).map_err(|e| format!("could not write {}: {e}", path.display()))
+}
+
+pub fn load_game(path: &Path) -> Result<Game, String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("could not read {}: {e}", path.display()))?;
+    let mut save: Value = serde_json::from_str(&text)
+        .map_err(|e| format!("{} is not a save file: {e}", path.display()))?;
+
+    match save["version"].as_u64() {
+        Some(SAVE_VERSION) => {}
+        Some(version) => return Err(format!(
+            "{} was saved by an incompatible version (format {version}, expected {SAVE_VERSION})",
+            path.display()
+        )),
+        None => return Err(format!("{} has no format version", path.display())),
+    }
+
+    let ecs = registry()
+        .as_deserialize()
+        .deserialize(save["world"].take())
+        .map_err(|e| format!("could not load entities: {e}"))?;
+
+    let mode: GameMode = field(&mut save, "mode")?;
+    let theme: ThemeKind = field(&mut save, "theme")?;
+
+    let mut resources = Resources::default();
+    resources.insert(field::<Map>(&mut save, "map")?);
+    resources.insert(field::<Camera>(&mut save, "camera")?);
+    resources.insert(mode.starting_state());
+    resources.insert(mode);
+    resources.insert(field::<Rules>(&mut save, "rules")?);
+    resources.insert(theme.theme());
+    resources.insert(field::<RandomNumberGenerator>(&mut save, "rng")?);
+    resources.insert(field::<Seed>(&mut save, "seed")?);
+    let clock: GameClock = field(&mut save, "clock")?;
+    resources.insert(field::<Floor>(&mut save, "floor")?);
+    resources.insert(field::<Layouts>(&mut save, "layouts")?);
+    if let Some(practice) = field::<Option<Practice>>(&mut save, "practice")? {
+        resources.insert(practice);
+    }
+    let mut log: MessageLog = field(&mut save, "log")?;
+    resources.insert(Templates::load_or_defaults(&mut log, &clock));
+    resources.insert(clock);
+    resources.insert(log);
+
+    Ok(Game::from_parts(ecs, resources, mode))
+}
+
+fn field<T: DeserializeOwned>(save: &mut Value, name: &str) -> Result<T, String> {
+    serde_json::from_value(save[name].take()).map_err(|e| format!("could not load {name}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Somewhere to save to that no other test is using
+    fn scratch_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("mt_septune_{name}_{}.json", std::process::id()))
+    }
+
+    // What can be told apart about every entity with a name, in a set order
+    fn snapshot(game: &Game) -> Vec<String> {
+        let mut entities: Vec<String> =
+            <(&Name, Option<&Point>, Option<&Health>, Option<&Brain>)>::query()
+                .iter(&game.ecs)
+                .map(|(name, pos, health, brain)| {
+                    format!("{} {pos:?} {health:?} {brain:?}", name.0)
+                })
+                .collect();
+        entities.sort();
+        entities
+    }
+
+    #[test]
+    fn a_saved_run_comes_back_as_it_was() {
+        for mode in [GameMode::RealTime, GameMode::TurnBased] {
+            let path = scratch_path(&format!("round_trip_{mode:?}"));
+            let mut played = Headless::new(Seed(11), mode, Rules::default());
+            played.run_script("llkkhhjj").unwrap();
+
+            save_game(&played.game, &path).unwrap();
+            let mut loaded = Headless {
+                game: load_game(&path).unwrap(),
+                frames: played.frames,
+            };
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!(loaded.game.ecs.len(), played.game.ecs.len());
+            assert_eq!(snapshot(&loaded.game), snapshot(&played.game));
+            assert_eq!(loaded.summary(), played.summary());
+            let tiles = |game: &Game| game.resources.get::<Map>().unwrap().tiles.clone();
+            assert!(tiles(&loaded.game) == tiles(&played.game));
+            let steps = |game: &Game| game.resources.get::<GameClock>().unwrap().steps;
+            assert_eq!(steps(&loaded.game), steps(&played.game));
+
+            // and carries on from there
+            loaded.run_script("lllljjjjhhhhkkkk").unwrap();
+        }
+    }
+
+    #[test]
+    fn a_save_from_another_version_is_refused() {
+        let path = scratch_path("old_version");
+        fs::write(&path, json!({ "version": SAVE_VERSION - 1 }).to_string()).unwrap();
+        let loaded = load_game(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(loaded.is_err());
+    }
+}
@@ -0,0 +1,16 @@
+use crate::prelude::*;
+
+// The seed a run was started from. Kept around as a resource so it can be
+// shown to the player and shared in bug reports.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Seed(pub u64);
+
+impl Seed {
+    pub fn random() -> Self {
+        Self(RandomNumberGenerator::new().next_u64())
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        text.trim().parse::<u64>().ok().map(Seed)
+    }
+}
@@ -0,0 +1,57 @@
+use crate::prelude::*;
+
+mod template;
+pub use template::*;
+
+pub fn spawn_player(ecs: &mut World, pos: Point) {
+    let player = ecs.push((
+        Player,
+        pos,
+        Render {
+            color: ColorPair::new(RGB::from_u8(255, 204, 51), BLACK),
+            glyph: to_cp437('@'),
+        },
+        Health {
+            current: 20,
+            max: 20,
+        },
+        Attack {
+            dice: 1,
+            sides: 6,
+            bonus: 0,
+            accuracy: 10,
+            crit_chance: 10,
+        },
+        Defense {
+            armor: 0,
+            evasion: 5,
+        },
+        FieldOfView::new(8),
+        // the player gets the first move
+        Energy {
+            current: ACTION_COST,
+            speed: NORMAL_SPEED,
+        },
+    ));
+    let mut entry = ecs.entry(player).unwrap();
+    entry.add_component(Experience::new());
+    entry.add_component(Kills::default());
+    entry.add_component(ItemsUsed::default());
+}
+
+pub fn spawn_cosmic_egg(ecs: &mut World, pos: Point) {
+    ecs.push((
+        Item,
+        CosmicEgg,
+        pos,
+        Render {
+            color: ColorPair::new(MAGENTA, BLACK),
+            glyph: to_cp437('Φ'),
+        },
+        Name("Cosmic Egg of Eternity".to_string()),
+        Description(
+            "Its surface is impossibly detailed. Whoever holds it holds the mystery of Septune."
+                .to_string(),
+        ),
+    ));
+}
@@ -0,0 +1,387 @@
+use crate::prelude::*;
+use std::fs;
+
+const TEMPLATE_PATH: &str = "resources/templates.ron";
+// The templates the game ships with, to fall back on should the file be
+// missing or broken
+const DEFAULT_TEMPLATES: &str = include_str!("../../resources/templates.ron");
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum EntityType {
+    Enemy,
+    Item,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum AiType {
+    // roams the floor until it spots the player
+    Chasing,
+    // stands where it was spawned until it spots the player, and goes back
+    // there afterwards
+    Guard,
+    // stumbles about without a thought in its head
+    Random,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum ItemEffect {
+    Healing(i32),
+    DungeonMap,
+    Damage(i32),
+    Confusion(i32),
+    Fear(i32),
+    // turns, then damage or healing every turn
+    Poison(i32, i32),
+    Slow(i32),
+    Regeneration(i32, i32),
+    Teleport,
+    Blink,
+    RevealEntities,
+}
+
+impl ItemEffect {
+    fn kind(self) -> EffectKind {
+        match self {
+            ItemEffect::Healing(amount) => EffectKind::Heal(amount),
+            ItemEffect::DungeonMap => EffectKind::RevealMap,
+            ItemEffect::Damage(amount) => EffectKind::Damage(amount),
+            ItemEffect::Confusion(turns) => EffectKind::Confuse(turns),
+            ItemEffect::Fear(turns) => EffectKind::Frighten(turns),
+            ItemEffect::Poison(turns, damage) => EffectKind::Poison(turns, damage),
+            ItemEffect::Slow(turns) => EffectKind::Slow(turns),
+            ItemEffect::Regeneration(turns, amount) => EffectKind::Regenerate(turns, amount),
+            ItemEffect::Teleport => EffectKind::Teleport,
+            ItemEffect::Blink => EffectKind::Blink,
+            ItemEffect::RevealEntities => EffectKind::RevealEntities,
+        }
+    }
+
+    // The amounts and numbers of turns the effect is given
+    fn magnitudes(self) -> Vec<i32> {
+        match self {
+            ItemEffect::Healing(n)
+            | ItemEffect::Damage(n)
+            | ItemEffect::Confusion(n)
+            | ItemEffect::Fear(n)
+            | ItemEffect::Slow(n) => vec![n],
+            ItemEffect::Poison(turns, n) | ItemEffect::Regeneration(turns, n) => vec![turns, n],
+            ItemEffect::DungeonMap
+            | ItemEffect::Teleport
+            | ItemEffect::Blink
+            | ItemEffect::RevealEntities => Vec::new(),
+        }
+    }
+}
+
+// One kind of monster or item, as described in the template file
+#[derive(Clone, Debug, Deserialize)]
+pub struct Template {
+    pub entity_type: EntityType,
+    pub name: String,
+    pub glyph: char,
+    pub color: (u8, u8, u8),
+    pub floors: (u32, u32),
+    pub weight: usize,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub hp: Option<i32>,
+    #[serde(default)]
+    pub speed: Option<i32>,
+    #[serde(default)]
+    pub ai: Option<AiType>,
+    #[serde(default)]
+    pub flee_below: Option<i32>,
+    #[serde(default)]
+    pub opens_doors: bool,
+    #[serde(default)]
+    pub sight: Option<i32>,
+    #[serde(default)]
+    pub attack: Option<Attack>,
+    #[serde(default)]
+    pub defense: Option<Defense>,
+    #[serde(default)]
+    pub effects: Vec<ItemEffect>,
+    #[serde(default)]
+    pub slot: Option<EquipmentSlot>,
+    #[serde(default)]
+    pub bonus: Option<StatBonus>,
+    #[serde(default)]
+    pub range: Option<i32>,
+    #[serde(default)]
+    pub charges: Option<i32>,
+    #[serde(default)]
+    pub radius: Option<i32>,
+    #[serde(default)]
+    pub xp: Option<i32>,
+    #[serde(default)]
+    pub on_death: Vec<ItemEffect>,
+}
+
+impl Template {
+    // Floors in the file count from 1, the Floor resource from 0
+    fn appears_on(&self, floor: &Floor) -> bool {
+        (self.floors.0..=self.floors.1).contains(&(floor.0 + 1))
+    }
+
+    // Whatever in the template can't be right, however it is used
+    fn check(&self) -> Result<(), String> {
+        let (first, last) = self.floors;
+        if first < 1 || last > Floor::LAST + 1 || first > last {
+            return Err(format!(
+                "{} appears on floors {first} to {last}, not within 1 to {}",
+                self.name,
+                Floor::LAST + 1
+            ));
+        }
+        let effects = self.effects.iter().chain(&self.on_death);
+        if let Some(effect) = effects
+            .copied()
+            .find(|effect| effect.magnitudes().iter().any(|n| *n < 1))
+        {
+            return Err(format!(
+                "{} has {effect:?}, but amounts and turns start at 1",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Templates {
+    pub entities: Vec<Template>,
+}
+
+impl Templates {
+    pub fn load() -> Result<Self, String> {
+        let text = fs::read_to_string(TEMPLATE_PATH)
+            .map_err(|e| format!("could not open {TEMPLATE_PATH}: {e}"))?;
+        Self::parse(&text).map_err(|e| format!("{TEMPLATE_PATH}: {e}"))
+    }
+
+    // The templates the game ships with
+    pub fn defaults() -> Self {
+        Self::parse(DEFAULT_TEMPLATES).expect("the default templates do not load")
+    }
+
+    // The templates from the file, or the ones the game shipped with should
+    // the file not load, in which case `log` says why
+    pub fn load_or_defaults(log: &mut MessageLog, clock: &GameClock) -> Self {
+        Self::load().unwrap_or_else(|e| {
+            let warning = format!("{}; using the default monsters and items.", capitalize(&e));
+            log.add(clock, Message::new().colored(warning, RED));
+            Self::defaults()
+        })
+    }
+
+    // Every template in `text`, as long as they all make sense
+    fn parse(text: &str) -> Result<Self, String> {
+        let templates: Self =
+            ron::de::from_str(text).map_err(|e| format!("could not parse: {e}"))?;
+        for template in &templates.entities {
+            template.check()?;
+        }
+        Ok(templates)
+    }
+
+    pub fn spawn_entities(
+        &self,
+        ecs: &mut World,
+        rng: &mut RandomNumberGenerator,
+        floor: &Floor,
+        spawn_points: &[Point],
+    ) {
+        // every template is in here once per point of weight, so picking
+        // uniformly from the table respects the weights
+        let spawn_table: Vec<&Template> = self
+            .entities
+            .iter()
+            .filter(|template| template.appears_on(floor))
+            .flat_map(|template| std::iter::repeat_n(template, template.weight))
+            .collect();
+
+        if spawn_table.is_empty() {
+            return;
+        }
+
+        for pos in spawn_points {
+            if let Some(template) = rng.random_slice_entry(&spawn_table) {
+                spawn_template(ecs, template, *pos);
+            }
+        }
+    }
+}
+
+fn spawn_template(ecs: &mut World, template: &Template, pos: Point) {
+    let (r, g, b) = template.color;
+    let entity = ecs.push((
+        pos,
+        Render {
+            color: ColorPair::new(RGB::from_u8(r, g, b), BLACK),
+            glyph: to_cp437(template.glyph),
+        },
+        Name(template.name.clone()),
+    ));
+    let mut entry = ecs.entry(entity).unwrap();
+
+    if let Some(description) = &template.description {
+        entry.add_component(Description(description.clone()));
+    }
+
+    match template.entity_type {
+        EntityType::Item => {
+            entry.add_component(Item);
+            if let Some(attack) = template.attack {
+                entry.add_component(attack);
+            }
+        }
+        EntityType::Enemy => {
+            let hp = template.hp.unwrap_or(1);
+            entry.add_component(Enemy);
+            entry.add_component(Health {
+                current: hp,
+                max: hp,
+            });
+            entry.add_component(FieldOfView::new(template.sight.unwrap_or(6)));
+            entry.add_component(Energy::new(template.speed.unwrap_or(NORMAL_SPEED)));
+            entry.add_component(template.attack.unwrap_or(Attack::UNARMED));
+            entry.add_component(template.defense.unwrap_or_default());
+            entry.add_component(GrantsXp(template.xp.unwrap_or(hp)));
+            if !template.on_death.is_empty() {
+                let effects = template.on_death.iter().map(|effect| effect.kind());
+                entry.add_component(OnDeath(effects.collect()));
+            }
+            let brain = |post| Brain {
+                state: AiState::Idle,
+                post,
+                flee_below: template.flee_below.unwrap_or(0),
+            };
+            match template.ai {
+                Some(AiType::Chasing) => entry.add_component(brain(None)),
+                Some(AiType::Guard) => entry.add_component(brain(Some(pos))),
+                Some(AiType::Random) => entry.add_component(MovingRandomly),
+                None => {}
+            }
+            if template.opens_doors {
+                entry.add_component(OpensDoors);
+            }
+        }
+    }
+
+    if let Some(slot) = template.slot {
+        entry.add_component(Equippable { slot });
+    }
+    if let Some(bonus) = template.bonus {
+        entry.add_component(bonus);
+    }
+    if let Some(range) = template.range {
+        entry.add_component(Ranged { range });
+    }
+    if let Some(charges) = template.charges {
+        entry.add_component(Charges(charges));
+    }
+    if let Some(radius) = template.radius {
+        entry.add_component(AreaOfEffect { radius });
+    }
+
+    template.effects.iter().for_each(|effect| match effect {
+        ItemEffect::Healing(amount) => entry.add_component(ProvidesHealing { amount: *amount }),
+        ItemEffect::DungeonMap => entry.add_component(ProvidesDungeonMap),
+        ItemEffect::Damage(amount) => entry.add_component(InflictsDamage { amount: *amount }),
+        ItemEffect::Confusion(turns) => entry.add_component(InflictsConfusion { turns: *turns }),
+        ItemEffect::Fear(turns) => entry.add_component(InflictsFear { turns: *turns }),
+        ItemEffect::Poison(turns, damage) => entry.add_component(InflictsPoison {
+            turns: *turns,
+            damage: *damage,
+        }),
+        ItemEffect::Slow(turns) => entry.add_component(InflictsSlow { turns: *turns }),
+        ItemEffect::Regeneration(turns, amount) => entry.add_component(ProvidesRegeneration {
+            turns: *turns,
+            amount: *amount,
+        }),
+        ItemEffect::Teleport => entry.add_component(ProvidesTeleport),
+        ItemEffect::Blink => entry.add_component(ProvidesBlink),
+        ItemEffect::RevealEntities => entry.add_component(RevealsEntities),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monster(name: &str, floors: (u32, u32), weight: usize) -> Template {
+        ron::de::from_str(&format!(
+            "Template(entity_type: Enemy, name: \"{name}\", glyph: 'm', color: (0, 0, 0), \
+             floors: ({}, {}), weight: {weight})",
+            floors.0, floors.1
+        ))
+        .unwrap()
+    }
+
+    // How many of each kind of thing turn up on `floor` out of `count` spawns
+    fn spawn_counts(templates: &Templates, floor: &Floor, count: i32) -> Vec<(String, usize)> {
+        let mut ecs = World::default();
+        let mut rng = RandomNumberGenerator::seeded(3);
+        let points: Vec<Point> = (0..count).map(|x| Point::new(x, 0)).collect();
+        templates.spawn_entities(&mut ecs, &mut rng, floor, &points);
+        templates
+            .entities
+            .iter()
+            .map(|template| {
+                let spawned = <&Name>::query()
+                    .iter(&ecs)
+                    .filter(|name| name.0 == template.name)
+                    .count();
+                (template.name.clone(), spawned)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_template_off_the_floors_there_are_is_refused() {
+        assert!(monster("Rat", (1, Floor::LAST + 1), 1).check().is_ok());
+        assert!(monster("Rat", (0, 2), 1).check().is_err());
+        assert!(monster("Rat", (2, Floor::LAST + 2), 1).check().is_err());
+        assert!(monster("Rat", (3, 2), 1).check().is_err());
+    }
+
+    #[test]
+    fn an_effect_of_nothing_is_refused() {
+        let mut template = monster("Rat", (1, 1), 1);
+        template.on_death = vec![ItemEffect::Poison(3, 0)];
+        assert!(template.check().is_err());
+    }
+
+    #[test]
+    fn a_broken_template_file_is_refused() {
+        assert!(Templates::parse("Templates(entities: [Template(").is_err());
+        assert!(Templates::defaults().entities.len() > 1);
+    }
+
+    #[test]
+    fn nothing_spawns_off_its_floors() {
+        let templates = Templates {
+            entities: vec![monster("Rat", (1, 1), 1), monster("Yeti", (3, 4), 10)],
+        };
+        let counts = spawn_counts(&templates, &Floor(0), 100);
+        assert_eq!(
+            counts,
+            vec![("Rat".to_string(), 100), ("Yeti".to_string(), 0)]
+        );
+        let counts = spawn_counts(&templates, &Floor(3), 100);
+        assert_eq!(
+            counts,
+            vec![("Rat".to_string(), 0), ("Yeti".to_string(), 100)]
+        );
+    }
+
+    #[test]
+    fn heavier_templates_spawn_more_often() {
+        let templates = Templates {
+            entities: vec![monster("Rat", (1, 1), 1), monster("Yeti", (1, 1), 9)],
+        };
+        let counts = spawn_counts(&templates, &Floor(0), 1000);
+        assert!(counts[1].1 > counts[0].1 * 4, "{:?}", counts);
+    }
+}
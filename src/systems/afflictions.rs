@@ -0,0 +1,97 @@
+use super::ai::flee_step;
+use crate::prelude::*;
+
+// Monsters that are confused or afraid act on that instead of their usual AI,
+// until the status effects system has it wear off
+#[system]
+#[read_component(Point)]
+#[read_component(Enemy)]
+#[read_component(Player)]
+#[read_component(Health)]
+#[read_component(Energy)]
+#[read_component(Confused)]
+#[read_component(Afraid)]
+pub fn afflictions(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] map: &Map,
+    #[resource] rng: &mut RandomNumberGenerator,
+) {
+    let player_pos = <&Point>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .copied()
+        .next()
+        .unwrap();
+
+    let mut afflicted = <(Entity, &Point, &Energy)>::query()
+        .filter(component::<Enemy>() & (component::<Confused>() | component::<Afraid>()));
+    for (entity, pos, _) in afflicted
+        .iter(ecs)
+        .filter(|(_, _, energy)| energy.is_ready())
+    {
+        let entry = ecs.entry_ref(*entity).unwrap();
+        if entry.get_component::<Confused>().is_ok() {
+            stumble(ecs, commands, map, rng, *entity, *pos);
+        } else {
+            flee(ecs, commands, map, *entity, *pos, player_pos);
+        }
+    }
+}
+
+// A step in a random direction, hitting whoever happens to be standing there
+fn stumble(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    map: &Map,
+    rng: &mut RandomNumberGenerator,
+    entity: Entity,
+    pos: Point,
+) {
+    let delta = *rng.random_slice_entry(map.steps()).unwrap();
+    let destination = delta + pos;
+    let victim = <(Entity, &Point)>::query()
+        .filter(component::<Health>())
+        .iter(ecs)
+        .find(|(_, other)| **other == destination)
+        .map(|(victim, _)| *victim)
+        .filter(|_| map.can_step(pos, delta));
+
+    if let Some(victim) = victim {
+        commands.push((
+            (),
+            WantsToAttack {
+                attacker: entity,
+                victim,
+                with: None,
+            },
+        ));
+    } else {
+        commands.push((
+            (),
+            WantsToMove {
+                entity,
+                destination,
+            },
+        ));
+    }
+}
+
+// Staying put when cornered
+fn flee(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    map: &Map,
+    entity: Entity,
+    pos: Point,
+    player_pos: Point,
+) {
+    let destination = flee_step(ecs, map, pos, player_pos).unwrap_or(pos);
+    commands.push((
+        (),
+        WantsToMove {
+            entity,
+            destination,
+        },
+    ));
+}
@@ -0,0 +1,379 @@
+use crate::prelude::*;
+
+// Every monster with a brain sizes up the situation whenever it's ready to
+// act, and carries on with whatever that leaves it doing: wandering about or
+// standing guard, chasing the player, looking for them where they were last
+// seen, running away when badly hurt, or heading back to its post.
+#[system]
+#[read_component(Point)]
+#[read_component(Brain)]
+#[read_component(FieldOfView)]
+#[read_component(Health)]
+#[read_component(Player)]
+#[read_component(Energy)]
+#[read_component(Ranged)]
+#[read_component(Name)]
+#[read_component(Render)]
+#[read_component(Confused)]
+#[read_component(Afraid)]
+#[read_component(MoveBlocked)]
+#[read_component(OpensDoors)]
+pub fn ai(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] map: &Map,
+    #[resource] rng: &mut RandomNumberGenerator,
+    #[resource] log: &mut MessageLog,
+    #[resource] clock: &GameClock,
+    #[resource] flow_fields: &mut FlowFields,
+) {
+    let (player, player_pos) = <(Entity, &Point)>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .map(|(entity, pos)| (*entity, *pos))
+        .next()
+        .unwrap();
+
+    // confused or frightened monsters are left to the afflictions system
+    let thinkers: Vec<(Entity, Point, Brain, bool, Health)> =
+        <(Entity, &Point, &Brain, &FieldOfView, &Health, &Energy)>::query()
+            .filter(!component::<Confused>() & !component::<Afraid>())
+            .iter(ecs)
+            .filter(|(_, _, _, _, _, energy)| energy.is_ready())
+            .map(|(entity, pos, brain, fov, health, _)| {
+                let sees_player = fov.visible_tiles.contains(&player_pos);
+                (*entity, *pos, *brain, sees_player, *health)
+            })
+            .collect();
+
+    let mut crowd = Crowd {
+        player,
+        player_pos,
+        occupied: <&Point>::query()
+            .filter(component::<Health>() & !component::<Player>())
+            .iter(ecs)
+            .copied()
+            .collect(),
+    };
+
+    for (entity, pos, brain, sees_player, health) in thinkers {
+        let entry = ecs.entry_ref(entity).unwrap();
+        let blocked = entry.get_component::<MoveBlocked>().ok().map(|b| b.0);
+        let doors = if entry.get_component::<OpensDoors>().is_ok() {
+            DoorState::Closed
+        } else {
+            DoorState::Open
+        };
+        // a move turned down last time is only reported the once
+        if blocked.is_some() {
+            commands.remove_component::<MoveBlocked>(entity);
+        }
+        let seen_at = if sees_player { Some(player_pos) } else { None };
+        let state = next_state(&brain, pos, seen_at, health);
+        if state != brain.state {
+            commands.add_component(entity, Brain { state, ..brain });
+            if state == AiState::Fleeing {
+                let (name, color) = display_name(ecs, entity);
+                log.add(
+                    clock,
+                    Message::new()
+                        .colored(capitalize(&name), color)
+                        .text(" turns to flee!"),
+                );
+            }
+        }
+
+        match state {
+            AiState::Idle if brain.post.is_none() => {
+                wander(commands, map, rng, &mut crowd, entity, pos);
+            }
+            AiState::Idle => {}
+            AiState::Chasing(_) if attack_if_able(ecs, commands, map, &crowd, entity, pos) => {}
+            AiState::Chasing(_) => {
+                let flow_field = flow_fields.get(doors == DoorState::Closed);
+                flow_field.update(map, player_pos, doors);
+                if let Some(step) = flow_field.next_step(map, pos, &crowd.occupied) {
+                    crowd.step_or_attack(commands, map, entity, pos, step, blocked);
+                }
+            }
+            AiState::Investigating(target) => {
+                if let Some(step) = first_step(map, pos, target, doors) {
+                    crowd.step_or_attack(commands, map, entity, pos, step, blocked);
+                }
+            }
+            AiState::Returning => {
+                let post = brain.post.unwrap_or(pos);
+                if let Some(step) = first_step(map, pos, post, doors) {
+                    crowd.step_or_attack(commands, map, entity, pos, step, blocked);
+                }
+            }
+            AiState::Fleeing => match flee_step(ecs, map, pos, player_pos) {
+                Some(destination) => {
+                    crowd.step_or_attack(commands, map, entity, pos, destination, blocked);
+                }
+                // cornered, and nothing left to do but fight
+                None if next_to(map, pos, player_pos) => {
+                    crowd.step_or_attack(commands, map, entity, pos, player_pos, blocked);
+                }
+                None => {}
+            },
+        }
+    }
+}
+
+// What a monster turns to, given whether it can see the player right now and
+// how badly hurt it is
+fn next_state(brain: &Brain, pos: Point, seen_at: Option<Point>, health: Health) -> AiState {
+    let hurt = health.current * 100 < health.max * brain.flee_below;
+    let at_post = brain.post.is_none_or(|post| post == pos);
+    match (seen_at, brain.state) {
+        (Some(_), _) if hurt => AiState::Fleeing,
+        (Some(player_pos), _) => AiState::Chasing(player_pos),
+        // out of sight is not out of mind
+        (None, AiState::Chasing(last_seen)) => AiState::Investigating(last_seen),
+        // a look from next door is as good as standing there
+        (None, AiState::Investigating(target))
+            if DistanceAlg::Pythagoras.distance2d(pos, target) >= 1.5 =>
+        {
+            AiState::Investigating(target)
+        }
+        (None, _) if !at_post => AiState::Returning,
+        (None, _) => AiState::Idle,
+    }
+}
+
+fn next_to(map: &Map, pos: Point, other: Point) -> bool {
+    let delta = other - pos;
+    map.steps().contains(&delta) && map.can_step(pos, delta)
+}
+
+// Attacks the player if they're in reach, returning whether it did
+fn attack_if_able(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    map: &Map,
+    crowd: &Crowd,
+    entity: Entity,
+    pos: Point,
+) -> bool {
+    let player_pos = crowd.player_pos;
+    // monsters that attack from afar do so as soon as they have a shot
+    let range = ecs.entry_ref(entity).ok().and_then(|entry| {
+        entry
+            .get_component::<Ranged>()
+            .ok()
+            .map(|ranged| ranged.range)
+    });
+    let in_range = range.is_some_and(|range| {
+        let distance = DistanceAlg::Pythagoras.distance2d(pos, player_pos);
+        distance > 1.5
+            && f64::from(distance) <= f64::from(range)
+            && map.has_line_of_fire(pos, player_pos)
+    });
+
+    if in_range || next_to(map, pos, player_pos) {
+        crowd.attack(commands, entity);
+        return true;
+    }
+    false
+}
+
+// The first step along the shortest way to `target`, for a monster that can
+// get through doors up to `doors`
+fn first_step(map: &Map, pos: Point, target: Point, doors: DoorState) -> Option<Point> {
+    if pos == target {
+        return None;
+    }
+    let path = a_star_search(
+        map.point2d_to_index(pos),
+        map.point2d_to_index(target),
+        &map.pathing(doors),
+    );
+    if path.success && path.steps.len() > 1 {
+        Some(map.index_to_point2d(path.steps[1]))
+    } else {
+        None
+    }
+}
+
+// The step that takes a monster furthest from `danger`, if any gets it away
+pub(super) fn flee_step(ecs: &SubWorld, map: &Map, pos: Point, danger: Point) -> Option<Point> {
+    let taken: Vec<Point> = <&Point>::query()
+        .filter(component::<Health>())
+        .iter(ecs)
+        .copied()
+        .collect();
+    let distance = |to: &Point| DistanceAlg::Pythagoras.distance2d(danger, *to);
+
+    map.steps()
+        .iter()
+        .filter(|delta| map.can_step(pos, **delta))
+        .map(|delta| *delta + pos)
+        .filter(|to| !taken.contains(to))
+        .filter(|to| distance(to) > distance(&pos))
+        .max_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap())
+}
+
+// Now and then a step in a random direction, as long as it's free
+fn wander(
+    commands: &mut CommandBuffer,
+    map: &Map,
+    rng: &mut RandomNumberGenerator,
+    crowd: &mut Crowd,
+    entity: Entity,
+    pos: Point,
+) {
+    if rng.range(0, 3) != 0 {
+        return;
+    }
+    let delta = *rng.random_slice_entry(map.steps()).unwrap();
+    if map.can_step(pos, delta) {
+        crowd.step_or_attack(commands, map, entity, pos, pos + delta, None);
+    }
+}
+
+// Where the player and every monster stand, kept up to date as monsters pick
+// their steps so that no two of them set off for the same tile
+struct Crowd {
+    player: Entity,
+    player_pos: Point,
+    occupied: Vec<Point>,
+}
+
+impl Crowd {
+    fn is_free(&self, pos: Point) -> bool {
+        pos != self.player_pos && !self.occupied.contains(&pos)
+    }
+
+    fn attack(&self, commands: &mut CommandBuffer, entity: Entity) {
+        commands.push((
+            (),
+            WantsToAttack {
+                attacker: entity,
+                victim: self.player,
+                with: None,
+            },
+        ));
+    }
+
+    // Moves onto `destination`, attacking the player if they're standing
+    // there. Anyone else in the way means waiting for them to move on, unless
+    // the monster was already turned away from `blocked` last time, in which
+    // case it tries to get round them instead.
+    fn step_or_attack(
+        &mut self,
+        commands: &mut CommandBuffer,
+        map: &Map,
+        entity: Entity,
+        pos: Point,
+        destination: Point,
+        blocked: Option<Point>,
+    ) {
+        if destination == self.player_pos {
+            self.attack(commands, entity);
+            return;
+        }
+        let destination = if self.is_free(destination) {
+            destination
+        } else if blocked.is_some() {
+            let distance = |to: &Point| DistanceAlg::Pythagoras.distance2d(destination, *to);
+            let detour = map
+                .steps()
+                .iter()
+                .filter(|delta| map.can_step(pos, **delta))
+                .map(|delta| pos + *delta)
+                .filter(|to| self.is_free(*to) && distance(to) < 1.5)
+                .min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap());
+            match detour {
+                Some(detour) => detour,
+                None => return,
+            }
+        } else {
+            return;
+        };
+
+        self.occupied.retain(|other| *other != pos);
+        self.occupied.push(destination);
+        commands.push((
+            (),
+            WantsToMove {
+                entity,
+                destination,
+            },
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HERE: Point = Point { x: 10, y: 10 };
+    const PLAYER: Point = Point { x: 14, y: 10 };
+    const NEXT_DOOR: Point = Point { x: 11, y: 11 };
+    const POST: Point = Point { x: 3, y: 3 };
+
+    fn brain(state: AiState, post: Option<Point>) -> Brain {
+        Brain {
+            state,
+            post,
+            flee_below: 25,
+        }
+    }
+
+    fn health(current: i32) -> Health {
+        Health { current, max: 100 }
+    }
+
+    #[test]
+    fn monsters_size_up_the_situation() {
+        use AiState::*;
+
+        // what it was doing, its post, whether it sees the player, its health,
+        // and what it should turn to
+        let cases = [
+            (Idle, None, false, 100, Idle),
+            (Idle, None, true, 100, Chasing(PLAYER)),
+            (Idle, Some(HERE), false, 100, Idle),
+            (Idle, Some(POST), false, 100, Returning),
+            (Idle, None, true, 10, Fleeing),
+            (Chasing(POST), None, true, 100, Chasing(PLAYER)),
+            (Chasing(POST), None, false, 100, Investigating(POST)),
+            (Chasing(POST), Some(HERE), false, 10, Investigating(POST)),
+            (Chasing(POST), None, true, 24, Fleeing),
+            (Chasing(POST), None, true, 25, Chasing(PLAYER)),
+            (Investigating(POST), None, false, 100, Investigating(POST)),
+            (Investigating(POST), None, true, 100, Chasing(PLAYER)),
+            (Investigating(HERE), None, false, 100, Idle),
+            (Investigating(NEXT_DOOR), None, false, 100, Idle),
+            (Investigating(HERE), Some(POST), false, 100, Returning),
+            (Fleeing, None, true, 10, Fleeing),
+            (Fleeing, None, false, 10, Idle),
+            (Fleeing, Some(POST), false, 10, Returning),
+            (Returning, Some(POST), false, 100, Returning),
+            (Returning, Some(HERE), false, 100, Idle),
+            (Returning, Some(POST), true, 100, Chasing(PLAYER)),
+        ];
+
+        for (state, post, sees_player, hp, expected) in cases {
+            let seen_at = if sees_player { Some(PLAYER) } else { None };
+            assert_eq!(
+                next_state(&brain(state, post), HERE, seen_at, health(hp)),
+                expected,
+                "{state:?} at {post:?}, seeing the player: {sees_player}, {hp} hp"
+            );
+        }
+    }
+
+    #[test]
+    fn monsters_that_never_flee_fight_to_the_end() {
+        let brave = Brain {
+            flee_below: 0,
+            ..brain(AiState::Idle, None)
+        };
+        assert_eq!(
+            next_state(&brave, HERE, Some(PLAYER), health(1)),
+            AiState::Chasing(PLAYER)
+        );
+    }
+}
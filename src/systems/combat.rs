@@ -1,34 +1,339 @@
 use crate::prelude::*;
 
+// Percent chance to hit for an attacker with no accuracy against a target
+// with no evasion
+const BASE_HIT_CHANCE: i32 = 75;
+// Taken off the accuracy of confused attackers
+const CONFUSED_ACCURACY: i32 = 25;
+
 #[system]
 #[read_component(WantsToAttack)]
 #[read_component(Player)]
+#[read_component(Name)]
+#[read_component(Render)]
+#[read_component(Attack)]
+#[read_component(Defense)]
+#[read_component(Equipped)]
+#[read_component(StatBonus)]
+#[read_component(Point)]
+#[read_component(Charges)]
+#[read_component(Confused)]
+#[read_component(ProvidesHealing)]
+#[read_component(ProvidesDungeonMap)]
+#[read_component(ProvidesRegeneration)]
+#[read_component(ProvidesTeleport)]
+#[read_component(ProvidesBlink)]
+#[read_component(InflictsDamage)]
+#[read_component(InflictsConfusion)]
+#[read_component(InflictsFear)]
+#[read_component(InflictsPoison)]
+#[read_component(InflictsSlow)]
+#[read_component(RevealsEntities)]
 #[write_component(Health)]
-pub fn combat(ecs: &mut SubWorld, commands: &mut CommandBuffer) {
+#[write_component(Energy)]
+#[write_component(ItemsUsed)]
+pub fn combat(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] rng: &mut RandomNumberGenerator,
+    #[resource] log: &mut MessageLog,
+    #[resource] clock: &GameClock,
+) {
     let mut attackers = <(Entity, &WantsToAttack)>::query();
 
-    let victims : Vec<(Entity, Entity)> = attackers
+    let victims : Vec<(Entity, Entity, Entity, Option<Entity>)> = attackers
         .iter(ecs)
-        .map(|(entity, attack)| (*entity, attack.victim) )
+        .map(|(entity, attack)| (*entity, attack.attacker, attack.victim, attack.with) )
         .collect();
 
-    victims.iter().for_each(|(message, victim)| {
-        let is_player = ecs
+    for (message, attacker, victim, with) in &victims {
+        spend_energy(ecs, *attacker, ATTACK_COST);
+
+        // a thrown or zapped item hits as hard as the item does, leaving the
+        // attacker's own weapons out of it
+        let attack = if let Some(item) = with {
+            ecs.entry_ref(*item)
+                .ok()
+                .and_then(|entry| entry.get_component::<Attack>().ok().copied())
+                .unwrap_or(Attack::UNARMED)
+        } else {
+            let attack = ecs
+                .entry_ref(*attacker)
+                .ok()
+                .and_then(|entry| entry.get_component::<Attack>().ok().copied())
+                .unwrap_or(Attack::UNARMED);
+            with_attack_bonus(attack, equipment_bonus(ecs, *attacker))
+        };
+        let defense = ecs
             .entry_ref(*victim)
-            .unwrap()
-            .get_component::<Player>()
-            .is_ok();
+            .ok()
+            .and_then(|entry| entry.get_component::<Defense>().ok().copied())
+            .unwrap_or_default();
+
+        let attack = if is_confused(ecs, *attacker) {
+            Attack {
+                accuracy: attack.accuracy - CONFUSED_ACCURACY,
+                ..attack
+            }
+        } else {
+            attack
+        };
+
+        let defense = with_defense_bonus(defense, equipment_bonus(ecs, *victim));
+        let outcome = roll_attack(&attack, defense, rng);
+
+        let attacker_name = display_name(ecs, *attacker);
+        let victim_name = display_name(ecs, *victim);
+        let by_player = is_player(ecs, *attacker);
+        log.add(
+            clock,
+            attack_message(by_player, &attacker_name, &victim_name, outcome),
+        );
+
+        // whatever the blow carries, like a spider's venom, comes along with
+        // any hit that lands
+        if outcome != AttackOutcome::Miss {
+            inflict(ecs, commands, with.unwrap_or(*attacker), *attacker, *victim);
+        }
+        if let Some(item) = with {
+            record_use(ecs, *attacker, *item);
+            use_up(ecs, commands, log, clock, *item, *victim);
+        }
 
-        if let Ok(mut health) = ecs
+        if let Ok(health) = ecs
             .entry_mut(*victim)
             .unwrap()
             .get_component_mut::<Health>()
         {
-            health.current -= 1;
-            if health.current < 1 && !is_player {
-                commands.remove(*victim);
+            let was_alive = health.current > 0;
+            health.current -= outcome.damage();
+            // the death system takes it from here, crediting whoever landed
+            // the blow that brought it down
+            if was_alive && health.current < 1 {
+                commands.add_component(*victim, Dead { killer: *attacker });
             }
         }
+
         commands.remove(*message);
-    });
-}
\ No newline at end of file
+    }
+}
+
+// Wands lose a charge and crumble once they run out, anything else is thrown
+// and lands at the victim's feet
+fn use_up(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    log: &mut MessageLog,
+    clock: &GameClock,
+    item: Entity,
+    victim: Entity,
+) {
+    let charges = ecs
+        .entry_ref(item)
+        .ok()
+        .and_then(|entry| entry.get_component::<Charges>().ok().copied());
+
+    match charges {
+        Some(Charges(left)) if left > 1 => commands.add_component(item, Charges(left - 1)),
+        Some(_) => {
+            let (name, color) = display_name(ecs, item);
+            log.add(
+                clock,
+                Message::new()
+                    .colored(capitalize(&name), color)
+                    .text(" crumbles to dust."),
+            );
+            commands.remove(item);
+        }
+        None => {
+            let landing = ecs
+                .entry_ref(victim)
+                .ok()
+                .and_then(|entry| entry.get_component::<Point>().ok().copied());
+            commands.remove_component::<Carried>(item);
+            if let Some(pos) = landing {
+                commands.add_component(item, pos);
+            }
+        }
+    }
+}
+
+fn is_confused(ecs: &SubWorld, entity: Entity) -> bool {
+    ecs.entry_ref(entity)
+        .is_ok_and(|entry| entry.get_component::<Confused>().is_ok())
+}
+
+// Hands the effects of `source` on to the victim of a hit made by `attacker`
+fn inflict(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    source: Entity,
+    attacker: Entity,
+    victim: Entity,
+) {
+    let (effects, target) = match (ecs.entry_ref(source), ecs.entry_ref(victim)) {
+        (Ok(source), Ok(victim)) => match victim.get_component::<Point>() {
+            Ok(pos) => (effects_of(&source), *pos),
+            Err(_) => return,
+        },
+        _ => return,
+    };
+    for kind in effects {
+        commands.push((
+            (),
+            Effect {
+                source: attacker,
+                kind,
+                target,
+                radius: 0,
+            },
+        ));
+    }
+}
+
+// Everything `owner` has equipped, added up
+fn equipment_bonus(ecs: &SubWorld, owner: Entity) -> StatBonus {
+    <(&Equipped, &StatBonus)>::query()
+        .iter(ecs)
+        .filter(|(equipped, _)| equipped.0 == owner)
+        .fold(StatBonus::default(), |total, (_, bonus)| total + *bonus)
+}
+
+fn with_attack_bonus(attack: Attack, bonus: StatBonus) -> Attack {
+    Attack {
+        bonus: attack.bonus + bonus.damage,
+        accuracy: attack.accuracy + bonus.accuracy,
+        crit_chance: attack.crit_chance + bonus.crit_chance,
+        ..attack
+    }
+}
+
+fn with_defense_bonus(defense: Defense, bonus: StatBonus) -> Defense {
+    Defense {
+        armor: defense.armor + bonus.armor,
+        evasion: defense.evasion + bonus.evasion,
+    }
+}
+
+fn roll_attack(
+    attack: &Attack,
+    defense: Defense,
+    rng: &mut RandomNumberGenerator,
+) -> AttackOutcome {
+    let hit_chance = (BASE_HIT_CHANCE + attack.accuracy - defense.evasion).clamp(5, 95);
+    if rng.roll_dice(1, 100) > hit_chance {
+        return AttackOutcome::Miss;
+    }
+
+    let mut damage = rng.roll_dice(attack.dice, attack.sides) + attack.bonus;
+    // criticals roll their damage twice and slip past armor
+    if rng.roll_dice(1, 100) <= attack.crit_chance {
+        damage += rng.roll_dice(attack.dice, attack.sides);
+        AttackOutcome::Critical(damage)
+    } else {
+        AttackOutcome::Hit(i32::max(1, damage - defense.armor))
+    }
+}
+
+fn attack_message(
+    by_player: bool,
+    (attacker, attacker_color): &(String, RGB),
+    (victim, victim_color): &(String, RGB),
+    outcome: AttackOutcome,
+) -> Message {
+    let message = Message::new().colored(capitalize(attacker), *attacker_color);
+
+    match outcome {
+        AttackOutcome::Miss => message
+            .text(if by_player { " miss " } else { " misses " })
+            .colored(victim, *victim_color)
+            .text("."),
+        AttackOutcome::Hit(damage) => message
+            .text(if by_player { " hit " } else { " hits " })
+            .colored(victim, *victim_color)
+            .text(format!(" for {damage} damage.")),
+        AttackOutcome::Critical(damage) => message
+            .text(if by_player { " land" } else { " lands" })
+            .colored(" a critical hit", ORANGE)
+            .text(" on ")
+            .colored(victim, *victim_color)
+            .text(format!(" for {damage} damage!")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROLLS: usize = 10_000;
+
+    // How many of ROLLS attacks land, one way or another
+    fn hits(attack: &Attack, defense: Defense) -> usize {
+        let mut rng = RandomNumberGenerator::seeded(5);
+        (0..ROLLS)
+            .map(|_| roll_attack(attack, defense, &mut rng))
+            .filter(|outcome| *outcome != AttackOutcome::Miss)
+            .count()
+    }
+
+    fn attack(dice: i32, bonus: i32, accuracy: i32, crit_chance: i32) -> Attack {
+        Attack {
+            dice,
+            sides: 1,
+            bonus,
+            accuracy,
+            crit_chance,
+        }
+    }
+
+    #[test]
+    fn every_attack_has_a_chance_either_way() {
+        let sure_thing = hits(&attack(1, 0, 1000, 0), Defense::default());
+        assert!((9300..=9700).contains(&sure_thing), "{}", sure_thing);
+
+        let hopeless = Defense {
+            armor: 0,
+            evasion: 1000,
+        };
+        let long_shot = hits(&attack(1, 0, 0, 0), hopeless);
+        assert!((300..=700).contains(&long_shot), "{}", long_shot);
+
+        let even = hits(&attack(1, 0, 0, 0), Defense::default());
+        assert!((7300..=7700).contains(&even), "{}", even);
+    }
+
+    #[test]
+    fn armor_soaks_up_all_but_one_damage() {
+        let mut rng = RandomNumberGenerator::seeded(5);
+        let plated = Defense {
+            armor: 100,
+            evasion: 0,
+        };
+        for _ in 0..100 {
+            let outcome = roll_attack(&attack(3, 1, 1000, 0), plated, &mut rng);
+            assert!(
+                outcome == AttackOutcome::Miss || outcome == AttackOutcome::Hit(1),
+                "{:?}",
+                outcome
+            );
+        }
+    }
+
+    #[test]
+    fn criticals_double_the_dice_and_slip_past_armor() {
+        let mut rng = RandomNumberGenerator::seeded(5);
+        let plated = Defense {
+            armor: 100,
+            evasion: 0,
+        };
+        for _ in 0..100 {
+            // three dice of one side each, twice over, and the bonus once
+            let outcome = roll_attack(&attack(3, 1, 1000, 100), plated, &mut rng);
+            assert!(
+                outcome == AttackOutcome::Miss || outcome == AttackOutcome::Critical(7),
+                "{:?}",
+                outcome
+            );
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use crate::prelude::*;
+
+// Deals with whatever ran out of health: the killer gets the credit, the body
+// is left behind with everything it carried or had on, and anything set to go
+// off when it dies does
+#[system]
+#[read_component(Dead)]
+#[read_component(Point)]
+#[read_component(Name)]
+#[read_component(Render)]
+#[read_component(Player)]
+#[read_component(Carried)]
+#[read_component(Equipped)]
+#[read_component(GrantsXp)]
+#[read_component(Experience)]
+#[read_component(OnDeath)]
+#[read_component(AreaOfEffect)]
+#[write_component(Kills)]
+pub fn death(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] log: &mut MessageLog,
+    #[resource] clock: &GameClock,
+) {
+    let dead: Vec<(Entity, Entity)> = <(Entity, &Dead)>::query()
+        .iter(ecs)
+        .map(|(entity, dead)| (*entity, dead.killer))
+        .collect();
+
+    for (victim, killer) in dead {
+        // the player is left lying where they fell, for the game over screen
+        // to find out what happened
+        if is_player(ecs, victim) {
+            log.add(clock, Message::new().colored("You die...", RED));
+            continue;
+        }
+
+        let (name, color) = display_name(ecs, victim);
+        reward_kill(ecs, commands, killer, victim);
+        count_kill(ecs, killer, victim);
+        log.add(
+            clock,
+            Message::new()
+                .colored(capitalize(&name), color)
+                .text(" dies."),
+        );
+
+        let pos = ecs
+            .entry_ref(victim)
+            .ok()
+            .and_then(|entry| entry.get_component::<Point>().ok().copied());
+        if let Some(pos) = pos {
+            leave_corpse(ecs, commands, victim, pos);
+            drop_belongings(ecs, commands, victim, pos);
+            go_off(ecs, commands, killer, victim, pos);
+        }
+        commands.remove(victim);
+    }
+}
+
+fn count_kill(ecs: &mut SubWorld, killer: Entity, victim: Entity) {
+    if killer == victim {
+        return;
+    }
+    let name = match ecs.entry_ref(victim) {
+        Ok(entry) => match entry.get_component::<Name>() {
+            Ok(name) => name.0.clone(),
+            Err(_) => return,
+        },
+        Err(_) => return,
+    };
+    if let Ok(mut entry) = ecs.entry_mut(killer) {
+        if let Ok(kills) = entry.get_component_mut::<Kills>() {
+            *kills.0.entry(name).or_insert(0) += 1;
+        }
+    }
+}
+
+fn leave_corpse(ecs: &SubWorld, commands: &mut CommandBuffer, victim: Entity, pos: Point) {
+    if let Ok(entry) = ecs.entry_ref(victim) {
+        if let (Ok(name), Ok(render)) = (
+            entry.get_component::<Name>(),
+            entry.get_component::<Render>(),
+        ) {
+            commands.push((
+                pos,
+                Render {
+                    color: render.color,
+                    glyph: to_cp437('%'),
+                },
+                Name(format!("{} corpse", name.0)),
+                Corpse,
+            ));
+        }
+    }
+}
+
+// Drops everything the victim carried or had on where they fell
+fn drop_belongings(ecs: &SubWorld, commands: &mut CommandBuffer, victim: Entity, pos: Point) {
+    let mut belongings: Vec<Entity> = <(Entity, &Carried)>::query()
+        .iter(ecs)
+        .filter(|(_, carried)| carried.0 == victim)
+        .map(|(item, _)| *item)
+        .collect();
+    belongings.extend(
+        <(Entity, &Equipped)>::query()
+            .iter(ecs)
+            .filter(|(_, equipped)| equipped.0 == victim)
+            .map(|(item, _)| *item),
+    );
+
+    for item in belongings {
+        commands.remove_component::<Carried>(item);
+        commands.remove_component::<Equipped>(item);
+        commands.add_component(item, pos);
+    }
+}
+
+// Sets off the victim's on-death effects, as if `killer` had used them there
+fn go_off(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    killer: Entity,
+    victim: Entity,
+    pos: Point,
+) {
+    if let Ok(entry) = ecs.entry_ref(victim) {
+        if let Ok(on_death) = entry.get_component::<OnDeath>() {
+            let radius = entry
+                .get_component::<AreaOfEffect>()
+                .map_or(0, |area| area.radius);
+            for kind in &on_death.0 {
+                commands.push((
+                    (),
+                    Effect {
+                        source: killer,
+                        kind: *kind,
+                        target: pos,
+                        radius,
+                    },
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_dead_monster_leaves_its_corpse_and_belongings_where_it_fell() {
+        let mut ecs = World::default();
+        let mut resources = Resources::default();
+        resources.insert(MessageLog::default());
+        resources.insert(GameClock::default());
+
+        let pos = Point::new(4, 7);
+        let player = ecs.push((Player, Point::zero()));
+        let orc = ecs.push((
+            Enemy,
+            pos,
+            Name("Orc".to_string()),
+            Render {
+                color: ColorPair::new(GREEN, BLACK),
+                glyph: to_cp437('o'),
+            },
+            Dead { killer: player },
+        ));
+        let potion = ecs.push((Item, Carried(orc), Name("Healing potion".to_string())));
+        let axe = ecs.push((Item, Equipped(orc), Name("Axe".to_string())));
+
+        Schedule::builder()
+            .add_system(death_system())
+            .build()
+            .execute(&mut ecs, &mut resources);
+
+        assert!(ecs.entry_ref(orc).is_err());
+        let corpses: Vec<(&Point, &Name)> = <(&Point, &Name)>::query()
+            .filter(component::<Corpse>())
+            .iter(&ecs)
+            .collect();
+        assert_eq!(corpses.len(), 1);
+        assert_eq!(*corpses[0].0, pos);
+        assert_eq!(corpses[0].1 .0, "Orc corpse");
+
+        for item in [potion, axe] {
+            let entry = ecs.entry_ref(item).unwrap();
+            assert_eq!(entry.get_component::<Point>(), Ok(&pos));
+            assert!(entry.get_component::<Carried>().is_err());
+            assert!(entry.get_component::<Equipped>().is_err());
+        }
+    }
+}
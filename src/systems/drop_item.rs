@@ -0,0 +1,53 @@
+use crate::prelude::*;
+
+#[system]
+#[read_component(WantsToDrop)]
+#[read_component(Point)]
+#[read_component(Name)]
+#[read_component(Render)]
+#[read_component(Player)]
+#[write_component(Energy)]
+pub fn drop_items(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] log: &mut MessageLog,
+    #[resource] clock: &GameClock,
+) {
+    let drops: Vec<(Entity, WantsToDrop)> = <(Entity, &WantsToDrop)>::query()
+        .iter(ecs)
+        .map(|(entity, drop)| (*entity, *drop))
+        .collect();
+
+    for (message, drop) in drops {
+        let pos = ecs
+            .entry_ref(drop.entity)
+            .ok()
+            .and_then(|entry| entry.get_component::<Point>().ok().copied());
+
+        // the item lands at the feet of whoever carried it
+        if let Some(pos) = pos {
+            commands.remove_component::<Carried>(drop.item);
+            commands.remove_component::<Equipped>(drop.item);
+            commands.add_component(drop.item, pos);
+
+            let (name, color) = display_name(ecs, drop.entity);
+            let (item_name, item_color) = display_name(ecs, drop.item);
+            let verb = if is_player(ecs, drop.entity) {
+                " drop "
+            } else {
+                " drops "
+            };
+            log.add(
+                clock,
+                Message::new()
+                    .colored(capitalize(&name), color)
+                    .text(verb)
+                    .colored(item_name, item_color)
+                    .text("."),
+            );
+            spend_energy(ecs, drop.entity, USE_ITEM_COST);
+        }
+
+        commands.remove(message);
+    }
+}
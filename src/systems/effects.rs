@@ -0,0 +1,224 @@
+use crate::prelude::*;
+
+#[system]
+#[read_component(Effect)]
+#[read_component(Point)]
+#[read_component(Name)]
+#[read_component(Render)]
+#[read_component(Player)]
+#[read_component(Enemy)]
+#[read_component(FieldOfView)]
+#[write_component(Health)]
+pub fn effects(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] map: &mut Map,
+    #[resource] camera: &mut Camera,
+    #[resource] rng: &mut RandomNumberGenerator,
+    #[resource] log: &mut MessageLog,
+    #[resource] clock: &GameClock,
+) {
+    let pending: Vec<(Entity, Effect)> = <(Entity, &Effect)>::query()
+        .iter(ecs)
+        .map(|(entity, effect)| (*entity, *effect))
+        .collect();
+
+    for (message, effect) in pending {
+        let targets = affected(ecs, map, &effect);
+        match effect.kind {
+            EffectKind::Heal(amount) => {
+                for target in targets {
+                    let amount = rng.range(1, amount.max(1) + 1);
+                    heal(ecs, log, clock, target, amount);
+                }
+            }
+            EffectKind::Damage(amount) => {
+                for target in targets {
+                    damage(ecs, commands, log, clock, effect.source, target, amount);
+                }
+            }
+            EffectKind::Confuse(turns) => {
+                for target in targets {
+                    commands.add_component(target, Confused { turns });
+                    let message = describe(ecs, target, " stumble", " stumbles");
+                    log.add(clock, message.text(" about, confused."));
+                }
+            }
+            EffectKind::Frighten(turns) => {
+                for target in targets {
+                    commands.add_component(target, Afraid { turns });
+                    let message = describe(ecs, target, " are", " is");
+                    log.add(clock, message.text(" gripped by terror."));
+                }
+            }
+            EffectKind::Poison(turns, damage) => {
+                let by = effect.source;
+                for target in targets {
+                    commands.add_component(target, Poisoned { turns, damage, by });
+                    let message = describe(ecs, target, " are", " is");
+                    log.add(clock, message.colored(" poisoned", GREEN).text("."));
+                }
+            }
+            EffectKind::Slow(turns) => {
+                for target in targets {
+                    commands.add_component(target, Slowed { turns });
+                    let message = describe(ecs, target, " slow", " slows");
+                    log.add(clock, message.text(" down."));
+                }
+            }
+            EffectKind::Regenerate(turns, amount) => {
+                for target in targets {
+                    commands.add_component(target, Regenerating { turns, amount });
+                    let message = describe(ecs, target, " feel", " looks");
+                    log.add(clock, message.text(" vigorous."));
+                }
+            }
+            EffectKind::Teleport => {
+                for target in targets {
+                    if let Some(destination) = random_free_tile(ecs, map, rng) {
+                        relocate(ecs, commands, camera, target, destination);
+                        let message = describe(ecs, target, " vanish", " vanishes");
+                        log.add(clock, message.text(" in a puff of smoke."));
+                    }
+                }
+            }
+            EffectKind::Blink => {
+                if map.can_enter_tile(effect.target) && !occupied(ecs, effect.target) {
+                    relocate(ecs, commands, camera, effect.source, effect.target);
+                } else {
+                    log.add(
+                        clock,
+                        Message::new().colored("The air shimmers, but nothing happens.", GRAY),
+                    );
+                }
+            }
+            EffectKind::RevealMap => {
+                // go through map tiles and make some of them revealed
+                map.revealed_tiles.iter_mut().for_each(|t| {
+                    if rng.roll_dice(1, 6) > 4 {
+                        *t = true;
+                    }
+                });
+                log.add(
+                    clock,
+                    Message::new().colored(
+                        "Glimpses of the surroundings flash before your eyes.",
+                        BEIGE,
+                    ),
+                );
+            }
+            EffectKind::RevealEntities => {
+                for enemy in <Entity>::query().filter(component::<Enemy>()).iter(ecs) {
+                    commands.add_component(*enemy, Revealed);
+                }
+                log.add(
+                    clock,
+                    Message::new().colored("You sense every creature on this floor.", BEIGE),
+                );
+            }
+        }
+        commands.remove(message);
+    }
+}
+
+// Everything with health the effect reaches. Walls shelter whatever is behind
+// them from a blast.
+fn affected(ecs: &SubWorld, map: &Map, effect: &Effect) -> Vec<Entity> {
+    let area = field_of_view_set(effect.target, effect.radius, map);
+    <(Entity, &Point)>::query()
+        .filter(component::<Health>())
+        .iter(ecs)
+        .filter(|(_, pos)| {
+            let distance = DistanceAlg::Pythagoras.distance2d(effect.target, **pos);
+            **pos == effect.target
+                || (area.contains(*pos) && f64::from(distance) <= f64::from(effect.radius))
+        })
+        .map(|(entity, _)| *entity)
+        .collect()
+}
+
+// "You <you_verb>" or "The Rat <verb>", for the rest of the sentence to follow
+fn describe(ecs: &SubWorld, entity: Entity, you_verb: &str, verb: &str) -> Message {
+    let (name, color) = display_name(ecs, entity);
+    let verb = if is_player(ecs, entity) {
+        you_verb
+    } else {
+        verb
+    };
+    Message::new().colored(capitalize(&name), color).text(verb)
+}
+
+fn heal(ecs: &mut SubWorld, log: &mut MessageLog, clock: &GameClock, target: Entity, amount: i32) {
+    let message = describe(ecs, target, " recover ", " recovers ");
+    if let Ok(mut entry) = ecs.entry_mut(target) {
+        if let Ok(health) = entry.get_component_mut::<Health>() {
+            // to prevent overhealing, clamp to max
+            let healed = i32::min(health.max, health.current + amount) - health.current;
+            health.current += healed;
+            log.add(
+                clock,
+                message.colored(format!("{healed} health"), GREEN).text("."),
+            );
+        }
+    }
+}
+
+fn damage(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    log: &mut MessageLog,
+    clock: &GameClock,
+    source: Entity,
+    target: Entity,
+    amount: i32,
+) {
+    let message = describe(ecs, target, " take ", " takes ");
+    if let Ok(mut entry) = ecs.entry_mut(target) {
+        if let Ok(health) = entry.get_component_mut::<Health>() {
+            let was_alive = health.current > 0;
+            health.current -= amount;
+            log.add(clock, message.text(format!("{amount} damage.")));
+            if was_alive && health.current < 1 {
+                commands.add_component(target, Dead { killer: source });
+            }
+        }
+    }
+}
+
+fn occupied(ecs: &SubWorld, pos: Point) -> bool {
+    <&Point>::query()
+        .filter(component::<Health>())
+        .iter(ecs)
+        .any(|other| *other == pos)
+}
+
+fn random_free_tile(ecs: &SubWorld, map: &Map, rng: &mut RandomNumberGenerator) -> Option<Point> {
+    let free: Vec<Point> = map
+        .tiles
+        .iter()
+        .enumerate()
+        .filter(|(_, tile)| **tile != TileType::Stairs)
+        .map(|(idx, _)| map.index_to_point2d(idx))
+        .filter(|pos| map.can_enter_tile(*pos) && !occupied(ecs, *pos))
+        .collect();
+    rng.random_slice_entry(&free).copied()
+}
+
+// Puts `entity` down at `destination` without it having to walk there
+fn relocate(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    camera: &mut Camera,
+    entity: Entity,
+    destination: Point,
+) {
+    commands.add_component(entity, destination);
+    if let Ok(entry) = ecs.entry_ref(entity) {
+        if let Ok(fov) = entry.get_component::<FieldOfView>() {
+            commands.add_component(entity, fov.clone_dirty());
+        }
+        if entry.get_component::<Player>().is_ok() {
+            camera.on_player_move(destination);
+        }
+    }
+}
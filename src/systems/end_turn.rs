@@ -5,29 +5,42 @@ use crate::prelude::*;
 #[read_component(Point)]
 #[read_component(Player)]
 #[read_component(CosmicEgg)]
-pub fn end_turn(ecs: &SubWorld, #[resource] turn_state: &mut TurnState) {
-    let mut player_hp = <(&Health, &Point)>::query().filter(component::<Player>());
+#[read_component(Carried)]
+#[read_component(Energy)]
+pub fn end_turn(ecs: &SubWorld, #[resource] turn_state: &mut TurnState, #[resource] map: &Map) {
+    let mut player_hp = <(Entity, &Health, &Point)>::query().filter(component::<Player>());
+    let mut player_energy = <&Energy>::query().filter(component::<Player>());
 
-    let mut cosmic_egg = <&Point>::query().filter(component::<CosmicEgg>());
+    let mut cosmic_egg = <&Carried>::query().filter(component::<CosmicEgg>());
 
     let current_state = turn_state.clone();
 
-    let mut new_state = current_state;
-    // let mut new_state = match current_state {
-    //     TurnState::AwaitingInput => return,
-    //     TurnState::PlayerTurn => TurnState::MonsterTurn,
-    //     TurnState::MonsterTurn => TurnState::AwaitingInput,
-    //     _ => current_state
-    // };
+    let mut new_state = match current_state {
+        TurnState::AwaitingInput => return,
+        TurnState::PlayerTurn => TurnState::MonsterTurn,
+        // Time keeps passing until the player has the energy to act again
+        TurnState::MonsterTurn => {
+            if player_energy.iter(ecs).all(|energy| energy.is_ready()) {
+                TurnState::AwaitingInput
+            } else {
+                TurnState::MonsterTurn
+            }
+        }
+        _ => current_state,
+    };
 
-    let cosmic_egg_pos = cosmic_egg.iter(ecs).nth(0).unwrap();
+    // the egg only lies on the last floor, and the run is won once it's picked up
+    let egg_carrier = cosmic_egg.iter(ecs).next().map(|carried| carried.0);
 
-    player_hp.iter(ecs).for_each(|(hp, pos)| {
+    player_hp.iter(ecs).for_each(|(player, hp, pos)| {
         if hp.current < 1 {
             new_state = TurnState::GameOver;
-        }
-        if pos == cosmic_egg_pos {
+        } else if new_state == TurnState::LevelUp {
+            // the perk gets picked first, anything else can wait a turn
+        } else if Some(*player) == egg_carrier {
             new_state = TurnState::Victory;
+        } else if map.tiles[map_idx(pos.x, pos.y)] == TileType::Stairs {
+            new_state = TurnState::NextFloor;
         }
     });
 
@@ -5,11 +5,13 @@ use crate::prelude::*;
 #[read_component(Render)]
 #[read_component(FieldOfView)]
 #[read_component(Player)]
+#[read_component(Revealed)]
+#[read_component(Corpse)]
 pub fn entity_render(
     #[resource] camera: &Camera,
     ecs: &SubWorld,
 ) {
-    let mut renderables = <(&Point, &Render)>::query();
+    let mut renderables = <(Entity, &Point, &Render)>::query();
     let mut fov = <&FieldOfView>::query().filter(component::<Player>());
 
     let mut draw_batch = DrawBatch::new();
@@ -18,17 +20,31 @@ pub fn entity_render(
 
     let player_fov = fov.iter(ecs).nth(0).unwrap();
 
-    renderables
+    // corpses go down first, so whatever stands or lies on top of one shows
+    let (corpses, others): (Vec<_>, Vec<_>) = renderables
         .iter(ecs)
-        .filter(|(pos, _)| player_fov.visible_tiles.contains(&pos))
-        .for_each(|(pos, render)| {
-            draw_batch.set(
-                *pos - offset,
-                render.color,
-                render.glyph
-            );
-        }
-    );
+        .filter(|(entity, pos, _)| {
+            player_fov.visible_tiles.contains(pos)
+                || ecs
+                    .entry_ref(**entity)
+                    .unwrap()
+                    .get_component::<Revealed>()
+                    .is_ok()
+        })
+        .partition(|(entity, _, _)| {
+            ecs.entry_ref(**entity)
+                .unwrap()
+                .get_component::<Corpse>()
+                .is_ok()
+        });
+
+    corpses.iter().chain(others.iter()).for_each(|(_, pos, render)| {
+        draw_batch.set(
+            **pos - offset,
+            render.color,
+            render.glyph
+        );
+    });
 
     draw_batch.submit(5000).expect("Batch error");
 }
@@ -0,0 +1,105 @@
+use crate::prelude::*;
+
+#[system]
+#[read_component(WantsToEquip)]
+#[read_component(WantsToUnequip)]
+#[read_component(Equippable)]
+#[read_component(Equipped)]
+#[read_component(Name)]
+#[read_component(Render)]
+#[read_component(Player)]
+#[write_component(Energy)]
+pub fn equip_items(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] log: &mut MessageLog,
+    #[resource] clock: &GameClock,
+) {
+    let unequips: Vec<(Entity, WantsToUnequip)> = <(Entity, &WantsToUnequip)>::query()
+        .iter(ecs)
+        .map(|(entity, unequip)| (*entity, *unequip))
+        .collect();
+
+    for (message, unequip) in unequips {
+        take_off(ecs, commands, log, clock, unequip.entity, unequip.item);
+        spend_energy(ecs, unequip.entity, USE_ITEM_COST);
+        commands.remove(message);
+    }
+
+    let equips: Vec<(Entity, WantsToEquip)> = <(Entity, &WantsToEquip)>::query()
+        .iter(ecs)
+        .map(|(entity, equip)| (*entity, *equip))
+        .collect();
+
+    for (message, equip) in equips {
+        let slot = ecs
+            .entry_ref(equip.item)
+            .ok()
+            .and_then(|entry| entry.get_component::<Equippable>().ok().map(|e| e.slot));
+
+        if let Some(slot) = slot {
+            // whatever was worn in the same slot goes back into the pack
+            let worn: Vec<Entity> = <(Entity, &Equipped, &Equippable)>::query()
+                .iter(ecs)
+                .filter(|(_, equipped, equippable)| {
+                    equipped.0 == equip.entity && equippable.slot == slot
+                })
+                .map(|(entity, _, _)| *entity)
+                .collect();
+            for item in worn {
+                take_off(ecs, commands, log, clock, equip.entity, item);
+            }
+
+            commands.remove_component::<Carried>(equip.item);
+            commands.add_component(equip.item, Equipped(equip.entity));
+
+            let (name, color) = display_name(ecs, equip.entity);
+            let (item_name, item_color) = display_name(ecs, equip.item);
+            let verb = match (slot, is_player(ecs, equip.entity)) {
+                (EquipmentSlot::Weapon, true) => " wield ",
+                (EquipmentSlot::Weapon, false) => " wields ",
+                (_, true) => " put on ",
+                (_, false) => " puts on ",
+            };
+            log.add(
+                clock,
+                Message::new()
+                    .colored(capitalize(&name), color)
+                    .text(verb)
+                    .colored(item_name, item_color)
+                    .text("."),
+            );
+            spend_energy(ecs, equip.entity, USE_ITEM_COST);
+        }
+
+        commands.remove(message);
+    }
+}
+
+fn take_off(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    log: &mut MessageLog,
+    clock: &GameClock,
+    owner: Entity,
+    item: Entity,
+) {
+    commands.remove_component::<Equipped>(item);
+    commands.add_component(item, Carried(owner));
+
+    let (name, color) = display_name(ecs, owner);
+    let (item_name, item_color) = display_name(ecs, item);
+    let verb = if is_player(ecs, owner) {
+        " take off "
+    } else {
+        " takes off "
+    };
+    log.add(
+        clock,
+        Message::new()
+            .colored(capitalize(&name), color)
+            .text(verb)
+            .colored(item_name, item_color)
+            .text("."),
+    );
+}
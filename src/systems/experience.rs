@@ -0,0 +1,121 @@
+use crate::prelude::*;
+
+// Adds up the experience earned this turn. Every level gained raises max
+// health and earns a perk, which the player picks on the level up screen.
+#[system]
+#[read_component(GainXp)]
+#[write_component(Experience)]
+#[write_component(Health)]
+pub fn experience(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] turn_state: &mut TurnState,
+    #[resource] log: &mut MessageLog,
+    #[resource] clock: &GameClock,
+) {
+    let gains: Vec<(Entity, GainXp)> = <(Entity, &GainXp)>::query()
+        .iter(ecs)
+        .map(|(entity, gain)| (*entity, *gain))
+        .collect();
+
+    for (message, gain) in gains {
+        if let Ok(mut entry) = ecs.entry_mut(gain.entity) {
+            let mut levels = 0;
+            if let Ok(experience) = entry.get_component_mut::<Experience>() {
+                experience.xp += gain.amount;
+                while experience.xp >= experience.next_level() {
+                    experience.xp -= experience.next_level();
+                    experience.level += 1;
+                    experience.perks += 1;
+                    levels += 1;
+                    log.add(
+                        clock,
+                        Message::new()
+                            .colored(format!("You reach level {}!", experience.level), GOLD),
+                    );
+                }
+                if experience.perks > 0 {
+                    *turn_state = TurnState::LevelUp;
+                }
+            }
+            if let Ok(health) = entry.get_component_mut::<Health>() {
+                health.max += levels * Experience::HEALTH_PER_LEVEL;
+                health.current += levels * Experience::HEALTH_PER_LEVEL;
+            }
+        }
+        commands.remove(message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Gives a fresh level 1 player `amount` experience, returning where that
+    // leaves them, their health and the turn state
+    fn gain(amount: i32) -> (Experience, Health, TurnState, Resources) {
+        let mut ecs = World::default();
+        let mut resources = Resources::default();
+        resources.insert(TurnState::AwaitingInput);
+        resources.insert(MessageLog::default());
+        resources.insert(GameClock::default());
+
+        let player = ecs.push((
+            Experience::new(),
+            Health {
+                current: 5,
+                max: 20,
+            },
+        ));
+        ecs.push((
+            (),
+            GainXp {
+                entity: player,
+                amount,
+            },
+        ));
+        Schedule::builder()
+            .add_system(experience_system())
+            .build()
+            .execute(&mut ecs, &mut resources);
+
+        assert_eq!(<&GainXp>::query().iter(&ecs).count(), 0);
+        let entry = ecs.entry_ref(player).unwrap();
+        let experience = *entry.get_component::<Experience>().unwrap();
+        let health = *entry.get_component::<Health>().unwrap();
+        let turn_state = *resources.get::<TurnState>().unwrap();
+        (experience, health, turn_state, resources)
+    }
+
+    #[test]
+    fn experience_short_of_the_next_level_is_kept() {
+        let (experience, health, turn_state, _) = gain(Experience::XP_PER_LEVEL - 1);
+        assert_eq!(experience.level, 1);
+        assert_eq!(experience.xp, Experience::XP_PER_LEVEL - 1);
+        assert_eq!(experience.perks, 0);
+        assert_eq!((health.current, health.max), (5, 20));
+        assert_eq!(turn_state, TurnState::AwaitingInput);
+    }
+
+    #[test]
+    fn reaching_the_next_level_earns_a_perk_and_health() {
+        let (experience, health, turn_state, _) = gain(Experience::XP_PER_LEVEL);
+        assert_eq!(experience.level, 2);
+        assert_eq!(experience.xp, 0);
+        assert_eq!(experience.perks, 1);
+        assert_eq!(health.max, 20 + Experience::HEALTH_PER_LEVEL);
+        assert_eq!(health.current, 5 + Experience::HEALTH_PER_LEVEL);
+        assert_eq!(turn_state, TurnState::LevelUp);
+    }
+
+    #[test]
+    fn every_level_takes_more_than_the_last() {
+        // 10 to reach level 2 and 20 more for level 3, with 5 left over
+        let (experience, health, _, resources) = gain(35);
+        assert_eq!(experience.level, 3);
+        assert_eq!(experience.xp, 5);
+        assert_eq!(experience.perks, 2);
+        assert_eq!(health.max, 20 + 2 * Experience::HEALTH_PER_LEVEL);
+        assert_eq!(resources.get::<MessageLog>().unwrap().entries.len(), 2);
+    }
+}
@@ -1,4 +1,7 @@
 use crate::prelude::*;
+use std::convert::TryFrom;
+
+const LOG_LINES: usize = 5;
 
 #[system]
 #[read_component(Health)]
@@ -6,7 +9,25 @@ use crate::prelude::*;
 #[read_component(Item)]
 #[read_component(Carried)]
 #[read_component(Name)]
-pub fn hud(ecs: &SubWorld) {
+#[read_component(Render)]
+#[read_component(Description)]
+#[read_component(Equippable)]
+#[read_component(Equipped)]
+#[read_component(StatBonus)]
+#[read_component(Ranged)]
+#[read_component(Confused)]
+#[read_component(Afraid)]
+#[read_component(Poisoned)]
+#[read_component(Slowed)]
+#[read_component(Regenerating)]
+#[read_component(Experience)]
+pub fn hud(
+    ecs: &SubWorld,
+    #[resource] seed: &Seed,
+    #[resource] floor: &Floor,
+    #[resource] log: &MessageLog,
+    #[resource] keymap: &Keymap,
+) {
     let mut health_query = <&Health>::query().filter(component::<Player>());
     let player_health = health_query.iter(ecs).nth(0).unwrap();
 
@@ -35,23 +56,127 @@ pub fn hud(ecs: &SubWorld) {
         .find_map(|(entity, _player)| Some(*entity))
         .unwrap();
 
-    let mut item_query = <(&Item, &Name, &Carried)>::query();
-    let mut y = 5;
+    draw_experience(&mut draw_batch, ecs);
+    draw_statuses(&mut draw_batch, ecs, player);
 
-    item_query
-        .iter(ecs)
-        .filter(|(_, _, carried)| carried.0 == player)
-        .for_each(|(_, name, _)| {
-            draw_batch.print(Point::new(3, y), format!("{} : {}", y - 4, &name.0));
-            y += 1;
-        });
-    if y > 5 {
+    // only the first nine stacks have a quick use key, the rest wait in the inventory
+    let stacks = carried_stacks(ecs, player);
+    let mut y = 5;
+    for (slot, stack) in (1..=9).zip(&stacks) {
+        draw_batch.print(Point::new(3, y), format!("{} : {}", slot, stack.label()));
+        y += 1;
+    }
+    if stacks.len() > 9 {
+        draw_batch.print_color(
+            Point::new(3, y),
+            format!("... {} more", stacks.len() - 9),
+            ColorPair::new(GRAY, BLACK),
+        );
+        y += 1;
+    }
+    if !stacks.is_empty() {
         draw_batch.print_color(
             Point::new(2, 3),
             "Items carried",
             ColorPair::new(YELLOW, BLACK),
         );
+        y += 1;
+    }
+
+    let equipment = equipped_items(ecs, player);
+    if !equipment.is_empty() {
+        draw_batch.print_color(Point::new(2, y), "Equipment", ColorPair::new(YELLOW, BLACK));
+        y += 2;
+        for item in &equipment {
+            let slot = item.slot.map_or(String::new(), |slot| format!("{slot:?}"));
+            draw_batch.print_color(
+                Point::new(3, y),
+                format!("{} : {}", slot, item.name),
+                ColorPair::new(item.color, BLACK),
+            );
+            y += 1;
+        }
     }
 
+    // newest message just above the bottom line, which is kept for the key help
+    let mut y = SCREEN_HEIGHT - 2;
+    for entry in log.latest(LOG_LINES).iter().rev() {
+        draw_log_entry(&mut draw_batch, Point::new(1, y), entry);
+        y -= 1;
+    }
+    draw_batch.print_color_right(
+        Point::new(SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1),
+        key_help(keymap),
+        ColorPair::new(GRAY, BLACK),
+    );
+
+    draw_batch.print_color_right(
+        Point::new(SCREEN_WIDTH - 1, 0),
+        format!("Seed: {}", seed.0),
+        ColorPair::new(GRAY, BLACK),
+    );
+    draw_batch.print_color_right(
+        Point::new(SCREEN_WIDTH - 1, 1),
+        format!("Floor {} of {}", floor.0 + 1, Floor::LAST + 1),
+        ColorPair::new(GOLD, BLACK),
+    );
+
     draw_batch.submit(10000).expect("HUD Batch Error");
 }
+
+// The keys for the actions that are easy to forget, as they are bound
+fn key_help(keymap: &Keymap) -> String {
+    [
+        (Action::Explore, "explore"),
+        (Action::CloseDoor, "close door"),
+        (Action::Inventory, "inventory"),
+        (Action::MessageLog, "message log"),
+        (Action::SaveAndQuit, "save and quit"),
+    ]
+    .iter()
+    .filter_map(|(action, label)| {
+        keymap
+            .key_for(*action)
+            .map(|key| format!("{key:?}: {label}"))
+    })
+    .collect::<Vec<String>>()
+    .join("  ")
+}
+
+// Progress towards the next level, right under the health bar
+fn draw_experience(draw_batch: &mut DrawBatch, ecs: &SubWorld) {
+    if let Some(experience) = <&Experience>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .next()
+    {
+        draw_batch.bar_horizontal(
+            Point::new(0, 2),
+            35,
+            experience.xp,
+            experience.next_level(),
+            ColorPair::new(GOLD, BLACK),
+        );
+        draw_batch.print_color_centered_at(
+            Point::new(15, 2),
+            format!(
+                " Level {}: {} / {} xp ",
+                experience.level,
+                experience.xp,
+                experience.next_level()
+            ),
+            ColorPair::new(BLACK, GOLD),
+        );
+    }
+}
+
+// Status effects, right of the health bar with the turns they have left
+fn draw_statuses(draw_batch: &mut DrawBatch, ecs: &SubWorld, player: Entity) {
+    let mut x = 36;
+    for (_, icon, color, turns) in statuses(ecs, player) {
+        let label = format!("{icon}{turns}");
+        let width = i32::try_from(label.chars().count()).unwrap_or(0);
+        draw_batch.print_color(Point::new(x, 1), label, ColorPair::new(color, BLACK));
+        x += width + 1;
+    }
+}
@@ -0,0 +1,180 @@
+use crate::prelude::*;
+
+// Picking up what lies at the player's feet and shutting doors next to them.
+// Both can't be done while travelling, when a key press only stops the player.
+#[system]
+#[read_component(Point)]
+#[read_component(Player)]
+#[read_component(Health)]
+#[read_component(Item)]
+#[read_component(Name)]
+#[read_component(Render)]
+#[read_component(FieldOfView)]
+#[read_component(Travelling)]
+#[write_component(Energy)]
+pub fn interact(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] input: &FrameInput,
+    #[resource] turn_state: &mut TurnState,
+    #[resource] log: &mut MessageLog,
+    #[resource] clock: &GameClock,
+    #[resource] map: &mut Map,
+) {
+    let (player, travelling, ready) = <(Entity, &Energy, Option<&Travelling>)>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .map(|(entity, energy, travelling)| (*entity, travelling.is_some(), energy.is_ready()))
+        .next()
+        .unwrap();
+    if travelling || !ready {
+        return;
+    }
+
+    let cost = match input.action {
+        Some(Action::PickUp) if pick_up_items(ecs, commands, log, clock) => USE_ITEM_COST,
+        Some(Action::CloseDoor) if close_door(ecs, commands, map, log, clock) => ACTION_COST,
+        _ => return,
+    };
+    spend_energy(ecs, player, cost);
+
+    // In turn-based play, doing anything at all ends the player's turn
+    if *turn_state == TurnState::AwaitingInput {
+        *turn_state = TurnState::PlayerTurn;
+    }
+}
+
+// Picks up everything lying on the player's tile, returning whether there was anything
+fn pick_up_items(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    log: &mut MessageLog,
+    clock: &GameClock,
+) -> bool {
+    let (player, player_pos) = <(Entity, &Point)>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .map(|(entity, pos)| (*entity, *pos))
+        .next()
+        .unwrap();
+
+    let mut picked_up = false;
+    let mut items = <(Entity, &Item, &Point)>::query();
+    items
+        .iter(ecs)
+        .filter(|(_entity, _item, &item_pos)| item_pos == player_pos)
+        .for_each(|(entity, _item, _item_pos)| {
+            let (name, color) = display_name(ecs, *entity);
+            log.add(
+                clock,
+                Message::new()
+                    .text("You pick up ")
+                    .colored(name, color)
+                    .text("."),
+            );
+            commands.remove_component::<Point>(*entity);
+            commands.add_component(*entity, Carried(player));
+            picked_up = true;
+        });
+    picked_up
+}
+
+// Shuts an open door next to the player, returning whether there was one that
+// nothing was standing in the way of
+fn close_door(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    map: &mut Map,
+    log: &mut MessageLog,
+    clock: &GameClock,
+) -> bool {
+    let player_pos = <&Point>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .copied()
+        .next()
+        .unwrap();
+    let doors: Vec<Point> = map
+        .steps()
+        .iter()
+        .map(|delta| player_pos + *delta)
+        .filter(|pos| map.door(*pos) == Some(DoorState::Open))
+        .collect();
+    if doors.is_empty() {
+        log.add(
+            clock,
+            Message::new().text("There is no open door next to you."),
+        );
+        return false;
+    }
+
+    // monsters and items alike keep a door from shutting
+    let in_the_way: Vec<Point> = <&Point>::query()
+        .filter(component::<Health>() | component::<Item>())
+        .iter(ecs)
+        .copied()
+        .collect();
+    if let Some(door) = doors.into_iter().find(|pos| !in_the_way.contains(pos)) {
+        map.set_door(door, DoorState::Closed);
+        <(Entity, &FieldOfView)>::query()
+            .iter(ecs)
+            .for_each(|(entity, fov)| commands.add_component(*entity, fov.clone_dirty()));
+        return true;
+    }
+    log.add(clock, Message::new().text("Something is in the way."));
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOOR: Point = Point { x: 5, y: 5 };
+
+    // Has the player, standing next to an open door with `blocker` standing
+    // in it, try to shut it, returning what became of the door
+    fn close_door_on(blocker: Option<Health>) -> DoorState {
+        let mut ecs = World::default();
+        let mut resources = Resources::default();
+        let mut map = Map::new();
+        map.set_door(DOOR, DoorState::Open);
+        resources.insert(map);
+        resources.insert(FrameInput {
+            action: Some(Action::CloseDoor),
+            ..FrameInput::default()
+        });
+        resources.insert(TurnState::AwaitingInput);
+        resources.insert(MessageLog::default());
+        resources.insert(GameClock::default());
+
+        ecs.push((
+            Player,
+            DOOR - Point::new(1, 0),
+            Energy {
+                current: ACTION_COST,
+                speed: NORMAL_SPEED,
+            },
+        ));
+        if let Some(health) = blocker {
+            ecs.push((DOOR, health));
+        }
+
+        Schedule::builder()
+            .add_system(interact_system())
+            .build()
+            .execute(&mut ecs, &mut resources);
+        let map = resources.get::<Map>().unwrap();
+        map.door(DOOR).unwrap()
+    }
+
+    #[test]
+    fn an_open_door_shuts_when_nothing_is_in_it() {
+        assert_eq!(close_door_on(None), DoorState::Closed);
+    }
+
+    #[test]
+    fn a_door_stays_open_while_someone_stands_in_it() {
+        let rat = Health { current: 1, max: 1 };
+        assert_eq!(close_door_on(Some(rat)), DoorState::Open);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::prelude::*;
+
+// Picks one of the perks on offer with the keys bound to them, until the
+// player has none left to spend
+#[system]
+#[read_component(Player)]
+#[write_component(Experience)]
+#[write_component(Health)]
+#[write_component(Attack)]
+#[write_component(Defense)]
+// the game mode is a resource, so it comes in by reference however small
+#[allow(clippy::trivially_copy_pass_by_ref)]
+pub fn level_up(
+    ecs: &mut SubWorld,
+    #[resource] input: &FrameInput,
+    #[resource] mode: &GameMode,
+    #[resource] turn_state: &mut TurnState,
+    #[resource] log: &mut MessageLog,
+    #[resource] clock: &GameClock,
+) {
+    let choice = match input.menu_action {
+        Some(Action::Perk(n)) => n.checked_sub(1),
+        _ => None,
+    };
+
+    let mut players = <(&mut Experience, &mut Health, &mut Attack, &mut Defense)>::query()
+        .filter(component::<Player>());
+    if let Some((experience, health, attack, defense)) = players.iter_mut(ecs).next() {
+        if let Some(perk) = choice.and_then(|n| Perk::ALL.get(n)) {
+            match perk {
+                Perk::Toughness => {
+                    health.max += 5;
+                    health.current += 5;
+                }
+                Perk::Might => attack.bonus += 1,
+                Perk::Precision => {
+                    attack.accuracy += 10;
+                    attack.crit_chance += 5;
+                }
+                Perk::Guard => defense.armor += 1,
+                Perk::Agility => defense.evasion += 10,
+            }
+            experience.perks -= 1;
+            log.add(
+                clock,
+                Message::new()
+                    .text("You gain ")
+                    .colored(perk.description(), GOLD)
+                    .text("."),
+            );
+        }
+
+        // back to the turn the level was gained in
+        if experience.perks < 1 {
+            *turn_state = mode.acting_state();
+        }
+    }
+}
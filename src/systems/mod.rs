@@ -1,76 +1,279 @@
 use crate::prelude::*;
+use legion::world::EntryRef;
 
-mod chasing;
+mod afflictions;
+mod ai;
 mod combat;
+mod death;
+mod drop_item;
+mod effects;
 mod end_turn;
+mod equip;
+mod experience;
 mod entity_render;
 mod fov;
 mod hud;
+mod interact;
+mod level_up;
 mod map_render;
 mod movement;
 mod player_input;
 mod random_move;
+mod status_effects;
+mod targeting;
+mod targeting_render;
+mod time_step;
 mod tooltips;
+mod travel;
 mod use_item;
 
-// pub fn build_input_scheduler() -> Schedule {
-//     Schedule::builder()
-//         .add_system(player_input::player_input_system())
-//         .add_system(fov::fov_system())
-//         .flush()
-//         .add_system(map_render::map_render_system())
-//         .add_system(entity_render::entity_render_system())
-//         .add_system(hud::hud_system())
-//         .add_system(tooltips::tooltips_system())
-//         .build()
-// }
-
-// pub fn build_player_scheduler() -> Schedule {
-//     Schedule::builder()
-//         .add_system(combat::combat_system())
-//         .flush()
-//         .add_system(movement::movement_system())
-//         .flush()
-//         .add_system(fov::fov_system())
-//         .flush()
-//         .add_system(map_render::map_render_system())
-//         .add_system(entity_render::entity_render_system())
-//         .add_system(hud::hud_system())
-//         .add_system(end_turn::end_turn_system())
-//         .build()
-// }
+// Charges an entity for an action it has taken
+pub fn spend_energy(ecs: &mut SubWorld, entity: Entity, cost: i32) {
+    if let Ok(mut entry) = ecs.entry_mut(entity) {
+        if let Ok(energy) = entry.get_component_mut::<Energy>() {
+            energy.current -= cost;
+        }
+    }
+}
+
+// What to call an entity in the message log, and in which color
+pub fn display_name(ecs: &SubWorld, entity: Entity) -> (String, RGB) {
+    if let Ok(entry) = ecs.entry_ref(entity) {
+        let color = entry
+            .get_component::<Render>()
+            .map_or(RGB::named(WHITE), |render| render.color.fg.to_rgb());
+        if entry.get_component::<Player>().is_ok() {
+            return ("you".to_string(), color);
+        }
+        if let Ok(name) = entry.get_component::<Name>() {
+            return (format!("the {}", name.0), color);
+        }
+    }
+    ("something".to_string(), RGB::named(WHITE))
+}
+
+// Whether `entity` is the player, who messages call "you"
+pub fn is_player(ecs: &SubWorld, entity: Entity) -> bool {
+    ecs.entry_ref(entity)
+        .is_ok_and(|entry| entry.get_component::<Player>().is_ok())
+}
+
+// Where the enemies `viewer` can see are standing, closest first
+pub fn visible_enemies<W: EntityStore>(ecs: &W, viewer: Entity) -> Vec<Point> {
+    let (origin, fov) = match ecs.entry_ref(viewer) {
+        Ok(entry) => match (
+            entry.get_component::<Point>(),
+            entry.get_component::<FieldOfView>(),
+        ) {
+            (Ok(pos), Ok(fov)) => (*pos, fov.visible_tiles.clone()),
+            _ => return Vec::new(),
+        },
+        Err(_) => return Vec::new(),
+    };
+
+    let mut enemies: Vec<Point> = <&Point>::query()
+        .filter(component::<Enemy>())
+        .iter(ecs)
+        .filter(|pos| fov.contains(pos))
+        .copied()
+        .collect();
+    enemies.sort_by(|a, b| {
+        let a = DistanceAlg::Pythagoras.distance2d(origin, *a);
+        let b = DistanceAlg::Pythagoras.distance2d(origin, *b);
+        a.partial_cmp(&b).unwrap()
+    });
+    enemies
+}
+
+// Queues up the experience `victim` was worth for `killer`, if the killer is
+// one to gain any
+pub fn reward_kill(ecs: &SubWorld, commands: &mut CommandBuffer, killer: Entity, victim: Entity) {
+    let learns = ecs
+        .entry_ref(killer)
+        .is_ok_and(|entry| entry.get_component::<Experience>().is_ok());
+    let worth = ecs
+        .entry_ref(victim)
+        .ok()
+        .and_then(|entry| entry.get_component::<GrantsXp>().ok().map(|xp| xp.0));
+    if let (true, Some(amount)) = (learns, worth) {
+        commands.push((
+            (),
+            GainXp {
+                entity: killer,
+                amount,
+            },
+        ));
+    }
+}
+
+// Notes down `user` using `item`, if the user is one to keep count
+pub fn record_use(ecs: &mut SubWorld, user: Entity, item: Entity) {
+    let name = ecs
+        .entry_ref(item)
+        .ok()
+        .and_then(|entry| entry.get_component::<Name>().ok().map(|name| name.0.clone()));
+    if let (Some(name), Ok(mut entry)) = (name, ecs.entry_mut(user)) {
+        if let Ok(used) = entry.get_component_mut::<ItemsUsed>() {
+            *used.0.entry(name).or_insert(0) += 1;
+        }
+    }
+}
+
+// What an item does when used, or what a monster does to whoever it hits
+pub fn effects_of(entry: &EntryRef) -> Vec<EffectKind> {
+    let mut effects = Vec::new();
+    if let Ok(healing) = entry.get_component::<ProvidesHealing>() {
+        effects.push(EffectKind::Heal(healing.amount));
+    }
+    if let Ok(damage) = entry.get_component::<InflictsDamage>() {
+        effects.push(EffectKind::Damage(damage.amount));
+    }
+    if let Ok(confusion) = entry.get_component::<InflictsConfusion>() {
+        effects.push(EffectKind::Confuse(confusion.turns));
+    }
+    if let Ok(fear) = entry.get_component::<InflictsFear>() {
+        effects.push(EffectKind::Frighten(fear.turns));
+    }
+    if let Ok(poison) = entry.get_component::<InflictsPoison>() {
+        effects.push(EffectKind::Poison(poison.turns, poison.damage));
+    }
+    if let Ok(slow) = entry.get_component::<InflictsSlow>() {
+        effects.push(EffectKind::Slow(slow.turns));
+    }
+    if let Ok(regeneration) = entry.get_component::<ProvidesRegeneration>() {
+        effects.push(EffectKind::Regenerate(
+            regeneration.turns,
+            regeneration.amount,
+        ));
+    }
+    if entry.get_component::<ProvidesTeleport>().is_ok() {
+        effects.push(EffectKind::Teleport);
+    }
+    if entry.get_component::<ProvidesBlink>().is_ok() {
+        effects.push(EffectKind::Blink);
+    }
+    if entry.get_component::<ProvidesDungeonMap>().is_ok() {
+        effects.push(EffectKind::RevealMap);
+    }
+    if entry.get_component::<RevealsEntities>().is_ok() {
+        effects.push(EffectKind::RevealEntities);
+    }
+    effects
+}
+
+// The status effects `entity` is under as (name, icon, color, turns left)
+pub fn statuses(ecs: &SubWorld, entity: Entity) -> Vec<(&'static str, char, RGB, i32)> {
+    let mut statuses = Vec::new();
+    if let Ok(entry) = ecs.entry_ref(entity) {
+        if let Ok(poisoned) = entry.get_component::<Poisoned>() {
+            statuses.push(("poisoned", '♣', RGB::named(GREEN), poisoned.turns));
+        }
+        if let Ok(confused) = entry.get_component::<Confused>() {
+            statuses.push(("confused", '?', RGB::named(MAGENTA), confused.turns));
+        }
+        if let Ok(afraid) = entry.get_component::<Afraid>() {
+            statuses.push(("afraid", '!', RGB::named(YELLOW), afraid.turns));
+        }
+        if let Ok(slowed) = entry.get_component::<Slowed>() {
+            statuses.push(("slowed", '≈', RGB::named(CYAN), slowed.turns));
+        }
+        if let Ok(regenerating) = entry.get_component::<Regenerating>() {
+            statuses.push(("regenerating", '♥', RGB::named(PINK), regenerating.turns));
+        }
+    }
+    statuses
+}
+
+pub fn build_input_scheduler() -> Schedule {
+    Schedule::builder()
+        .add_system(player_input::player_input_system())
+        .add_system(interact::interact_system())
+        // travel picks up wherever player_input left it
+        .flush()
+        .add_system(travel::travel_system())
+        .flush()
+        .add_system(fov::fov_system())
+        .build()
+}
+
+pub fn build_targeting_scheduler() -> Schedule {
+    Schedule::builder()
+        .add_system(targeting::targeting_system())
+        .flush()
+        .build()
+}
+
+pub fn build_level_up_scheduler() -> Schedule {
+    Schedule::builder()
+        .add_system(level_up::level_up_system())
+        .build()
+}
+
+pub fn build_player_scheduler() -> Schedule {
+    Schedule::builder()
+        .add_system(use_item::use_items_system())
+        .add_system(drop_item::drop_items_system())
+        .add_system(equip::equip_items_system())
+        .add_system(combat::combat_system())
+        .flush()
+        .add_system(effects::effects_system())
+        .add_system(movement::movement_system())
+        .flush()
+        .add_system(death::death_system())
+        .flush()
+        .add_system(fov::fov_system())
+        .add_system(experience::experience_system())
+        .flush()
+        .add_system(end_turn::end_turn_system())
+        .build()
+}
 
 pub fn build_monster_scheduler() -> Schedule {
     Schedule::builder()
+        .add_system(time_step::time_step_system())
+        .flush()
+        .add_system(status_effects::status_effects_system())
+        .flush()
         .add_system(random_move::random_move_system())
-        .add_system(chasing::chasing_system())
+        .add_system(ai::ai_system())
+        .add_system(afflictions::afflictions_system())
         .flush()
         .add_system(use_item::use_items_system())
         .add_system(combat::combat_system())
-        // .flush()
-        // .add_system(movement::movement_system())
-        // .flush()
-        // .add_system(fov::fov_system())
-        // .flush()
-        // .add_system(map_render::map_render_system())
-        // .add_system(entity_render::entity_render_system())
-        // .add_system(hud::hud_system())
-        // .add_system(end_turn::end_turn_system())
+        .flush()
+        .add_system(effects::effects_system())
+        .add_system(movement::movement_system())
+        .flush()
+        .add_system(death::death_system())
+        .flush()
+        .add_system(fov::fov_system())
+        .add_system(experience::experience_system())
+        .flush()
+        .add_system(end_turn::end_turn_system())
         .build()
 }
 
 pub fn build_realtime_scheduler() -> Schedule {
     Schedule::builder()
         .add_system(player_input::player_input_system())
+        .add_system(interact::interact_system())
+        .flush()
+        .add_system(travel::travel_system())
         // .add_system(random_move::random_move_system())
-        // .add_system(chasing::chasing_system())
+        // .add_system(ai::ai_system())
         .flush()
         .add_system(use_item::use_items_system())
+        .add_system(drop_item::drop_items_system())
+        .add_system(equip::equip_items_system())
         .add_system(combat::combat_system())
         .flush()
+        .add_system(effects::effects_system())
         .add_system(movement::movement_system())
         .flush()
+        .add_system(death::death_system())
+        .flush()
         .add_system(fov::fov_system())
+        .add_system(experience::experience_system())
         .flush()
         //        .add_system(map_render::map_render_system())
         //        .add_system(entity_render::entity_render_system())
@@ -86,5 +289,6 @@ pub fn build_render_scheduler() -> Schedule {
         .add_system(entity_render::entity_render_system())
         .add_system(hud::hud_system())
         .add_system(tooltips::tooltips_system())
+        .add_system(targeting_render::targeting_render_system())
         .build()
 }
@@ -1,25 +1,109 @@
 use crate::prelude::*;
 
-#[system(for_each)]
+// A move someone has asked for, from where they stood at the time
+struct Move {
+    entity: Entity,
+    from: Option<Point>,
+    to: Point,
+}
+
+// Carries out everyone's moves at once, so that nobody ends up sharing a tile
+// however stale the positions they were worked out from. Monsters turned away
+// are told so with a MoveBlocked. Stepping into a shut door opens it instead,
+// for those who can.
+#[system]
+#[read_component(WantsToMove)]
+#[read_component(Point)]
+#[read_component(Health)]
 #[read_component(Player)]
+#[read_component(Brain)]
+#[read_component(OpensDoors)]
 #[read_component(FieldOfView)]
+#[write_component(Energy)]
 pub fn movement(
-    entity: &Entity,
-    want_move: &WantsToMove,
-    #[resource] map: &mut Map,
-    #[resource] camera: &mut Camera,
     ecs: &mut SubWorld,
     commands: &mut CommandBuffer,
+    #[resource] map: &mut Map,
+    #[resource] camera: &mut Camera,
+    #[resource] rng: &mut RandomNumberGenerator,
+    #[resource] log: &mut MessageLog,
+    #[resource] clock: &GameClock,
 ) {
-    if map.can_enter_tile(want_move.destination) {
-        commands.add_component(want_move.entity, want_move.destination);
+    let messages: Vec<(Entity, WantsToMove)> = <(Entity, &WantsToMove)>::query()
+        .iter(ecs)
+        .map(|(entity, want_move)| (*entity, *want_move))
+        .collect();
+    if messages.is_empty() {
+        return;
+    }
+
+    let player = <Entity>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .copied()
+        .next();
+    let mut moves: Vec<Move> = Vec::new();
+    for (message, want_move) in messages {
+        commands.remove(message);
+        // one move a turn is all anyone gets, or pays for
+        if moves.iter().any(|other| other.entity == want_move.entity) {
+            continue;
+        }
+        spend_energy(ecs, want_move.entity, MOVE_COST);
+        let from = ecs
+            .entry_ref(want_move.entity)
+            .ok()
+            .and_then(|entry| entry.get_component::<Point>().ok().copied());
+        moves.push(Move {
+            entity: want_move.entity,
+            from,
+            to: want_move.destination,
+        });
+    }
+    // the player goes first, then everyone else from the top left down
+    moves.sort_by_key(|m| (Some(m.entity) != player, m.from.map(|pos| (pos.y, pos.x))));
 
-        if let Ok(entry) = ecs.entry_ref(want_move.entity) {
+    let (door_bumps, moves): (Vec<Move>, Vec<Move>) = moves.into_iter().partition(|m| {
+        let shut = map.door(m.to).is_some_and(|state| state != DoorState::Open);
+        shut && m
+            .from
+            .is_some_and(|from| map.can_step_through(from, m.to - from, DoorState::Locked))
+    });
+    let mut opened = false;
+    for m in &door_bumps {
+        opened |= open_door(ecs, map, rng, log, clock, m);
+    }
+    // a door swinging open changes what everyone can see
+    if opened {
+        <(Entity, &FieldOfView)>::query()
+            .iter(ecs)
+            .for_each(|(entity, fov)| commands.add_component(*entity, fov.clone_dirty()));
+    }
+
+    let standing: Vec<(Entity, Point)> = <(Entity, &Point)>::query()
+        .filter(component::<Health>())
+        .iter(ecs)
+        .map(|(entity, pos)| (*entity, *pos))
+        .collect();
+
+    for (m, allowed) in moves.iter().zip(resolve(map, &standing, &moves)) {
+        if !allowed {
+            let thinks = ecs
+                .entry_ref(m.entity)
+                .is_ok_and(|entry| entry.get_component::<Brain>().is_ok());
+            if thinks {
+                commands.add_component(m.entity, MoveBlocked(m.to));
+            }
+            continue;
+        }
+
+        commands.add_component(m.entity, m.to);
+        if let Ok(entry) = ecs.entry_ref(m.entity) {
             if let Ok(fov) = entry.get_component::<FieldOfView>() {
-                commands.add_component(want_move.entity, fov.clone_dirty());
+                commands.add_component(m.entity, fov.clone_dirty());
 
                 if entry.get_component::<Player>().is_ok() {
-                    camera.on_player_move(want_move.destination);
+                    camera.on_player_move(m.to);
                     fov.visible_tiles.iter().for_each(|pos| {
                         if map.in_bounds(*pos) {
                             map.revealed_tiles[map_idx(pos.x, pos.y)] = true;
@@ -29,5 +113,316 @@ pub fn movement(
             }
         }
     }
-    commands.remove(*entity);
+}
+
+// Opens the shut door someone has stepped into, if they can, returning whether
+// it did. Only the player gets through locked doors, and it can take them a
+// few goes to force one.
+fn open_door(
+    ecs: &SubWorld,
+    map: &mut Map,
+    rng: &mut RandomNumberGenerator,
+    log: &mut MessageLog,
+    clock: &GameClock,
+    m: &Move,
+) -> bool {
+    let (player, opens_doors) = match ecs.entry_ref(m.entity) {
+        Ok(entry) => (
+            entry.get_component::<Player>().is_ok(),
+            entry.get_component::<OpensDoors>().is_ok(),
+        ),
+        Err(_) => return false,
+    };
+    let opens = match map.door(m.to) {
+        Some(DoorState::Closed) => player || opens_doors,
+        Some(DoorState::Locked) if player => {
+            let forced = rng.range(0, 3) == 0;
+            log.add(
+                clock,
+                Message::new().text(if forced {
+                    "You force the locked door open."
+                } else {
+                    "The door is locked. You throw your weight against it."
+                }),
+            );
+            forced
+        }
+        _ => false,
+    };
+    if opens {
+        map.set_door(m.to, DoorState::Open);
+    }
+    opens
+}
+
+// Which of the `moves` go ahead, given who is `standing` where. Every move is
+// a single step that keeps to the run's rules on diagonals. Of several movers
+// after the same tile the first whose move works out gets it, and a tile
+// someone is standing on can only be taken once they move off it, which lets
+// monsters follow one another in a line, swap places or go round in a circle.
+fn resolve(map: &Map, standing: &[(Entity, Point)], moves: &[Move]) -> Vec<bool> {
+    let mut verdicts: Vec<Option<bool>> = moves
+        .iter()
+        .map(|m| {
+            let allowed = match m.from {
+                Some(from) => map.can_step(from, m.to - from),
+                None => map.can_enter_tile(m.to),
+            };
+            if allowed {
+                None
+            } else {
+                Some(false)
+            }
+        })
+        .collect();
+    // for every move, the moves of whoever is standing where it leads, or
+    // None for anyone staying put
+    let in_the_way: Vec<Vec<Option<usize>>> = moves
+        .iter()
+        .map(|m| {
+            standing
+                .iter()
+                .filter(|(entity, pos)| *pos == m.to && *entity != m.entity)
+                .map(|(occupant, _)| moves.iter().position(|other| other.entity == *occupant))
+                .collect()
+        })
+        .collect();
+
+    while verdicts.contains(&None) {
+        if settle(moves, &in_the_way, &mut verdicts) {
+            continue;
+        }
+        // whatever is still undecided waits on a ring of movers each stepping
+        // into the tile the next one leaves, and they all go together
+        let ring = ring(&in_the_way, &verdicts);
+        if ring.is_empty() {
+            break;
+        }
+        for i in ring {
+            verdicts[i] = Some(true);
+        }
+    }
+    verdicts.into_iter().map(|v| v == Some(true)).collect()
+}
+
+// Decides every move it can, returning whether it decided any. A move goes
+// ahead once everyone in its way is known to be moving off and every earlier
+// mover after the same tile is known not to get there, and is turned down as
+// soon as one of them isn't, or another mover is known to get there first.
+fn settle(
+    moves: &[Move],
+    in_the_way: &[Vec<Option<usize>>],
+    verdicts: &mut [Option<bool>],
+) -> bool {
+    let mut changed = false;
+    for i in 0..moves.len() {
+        if verdicts[i].is_some() {
+            continue;
+        }
+        let rivals: Vec<usize> = (0..moves.len())
+            .filter(|j| *j != i && moves[*j].to == moves[i].to)
+            .collect();
+        let leaving: Vec<Option<bool>> = in_the_way[i]
+            .iter()
+            .map(|occupant| match occupant {
+                Some(j) => verdicts[*j],
+                // whoever isn't moving at all stays in the way
+                None => Some(false),
+            })
+            .collect();
+
+        let beaten = rivals.iter().any(|j| verdicts[*j] == Some(true));
+        if beaten || leaving.contains(&Some(false)) {
+            verdicts[i] = Some(false);
+            changed = true;
+        } else if rivals.iter().all(|j| *j > i || verdicts[*j] == Some(false))
+            && !leaving.contains(&None)
+        {
+            verdicts[i] = Some(true);
+            changed = true;
+        }
+    }
+    changed
+}
+
+// The first ring of undecided moves each waiting on the next one's mover to
+// step out of its way, or nothing if there is none
+fn ring(in_the_way: &[Vec<Option<usize>>], verdicts: &[Option<bool>]) -> Vec<usize> {
+    let next = |i: usize| {
+        in_the_way[i]
+            .iter()
+            .flatten()
+            .copied()
+            .find(|j| verdicts[*j].is_none())
+    };
+    for start in (0..verdicts.len()).filter(|i| verdicts[*i].is_none()) {
+        let mut path = vec![start];
+        while let Some(j) = next(path[path.len() - 1]) {
+            if let Some(at) = path.iter().position(|k| *k == j) {
+                return path.split_off(at);
+            }
+            path.push(j);
+        }
+    }
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let mut ecs = World::default();
+        (0..count).map(|_| ecs.push(())).collect()
+    }
+
+    fn step(entity: Entity, from: (i32, i32), to: (i32, i32)) -> Move {
+        Move {
+            entity,
+            from: Some(Point::from_tuple(from)),
+            to: Point::from_tuple(to),
+        }
+    }
+
+    // Everyone who moves stands where they move from
+    fn standing(moves: &[Move]) -> Vec<(Entity, Point)> {
+        moves.iter().map(|m| (m.entity, m.from.unwrap())).collect()
+    }
+
+    #[test]
+    fn only_the_first_mover_gets_a_contested_tile() {
+        let e = entities(2);
+        let moves = [step(e[0], (1, 1), (2, 1)), step(e[1], (3, 1), (2, 1))];
+        let verdicts = resolve(&Map::new(), &standing(&moves), &moves);
+        assert_eq!(verdicts, vec![true, false]);
+    }
+
+    #[test]
+    fn a_contested_tile_goes_to_the_first_mover_that_can_get_there() {
+        let e = entities(2);
+        // a diagonal step, in a run without them
+        let moves = [step(e[0], (1, 2), (2, 1)), step(e[1], (3, 1), (2, 1))];
+        let verdicts = resolve(&Map::new(), &standing(&moves), &moves);
+        assert_eq!(verdicts, vec![false, true]);
+    }
+
+    #[test]
+    fn two_movers_can_swap_places() {
+        let e = entities(2);
+        let moves = [step(e[0], (1, 1), (2, 1)), step(e[1], (2, 1), (1, 1))];
+        let verdicts = resolve(&Map::new(), &standing(&moves), &moves);
+        assert_eq!(verdicts, vec![true, true]);
+    }
+
+    #[test]
+    fn a_ring_of_three_goes_round_together() {
+        let mut map = Map::new();
+        map.diagonals = true;
+        let e = entities(3);
+        let moves = [
+            step(e[0], (1, 1), (2, 1)),
+            step(e[1], (2, 1), (2, 2)),
+            step(e[2], (2, 2), (1, 1)),
+        ];
+        let verdicts = resolve(&map, &standing(&moves), &moves);
+        assert_eq!(verdicts, vec![true, true, true]);
+    }
+
+    #[test]
+    fn a_line_stops_behind_a_leader_that_is_blocked() {
+        let e = entities(4);
+        let moves = [
+            step(e[0], (1, 1), (2, 1)),
+            step(e[1], (2, 1), (3, 1)),
+            step(e[2], (3, 1), (4, 1)),
+        ];
+        let mut standing = standing(&moves);
+        // the leader walks into someone who isn't going anywhere
+        standing.push((e[3], Point::new(4, 1)));
+        let verdicts = resolve(&Map::new(), &standing, &moves);
+        assert_eq!(verdicts, vec![false, false, false]);
+    }
+
+    #[test]
+    fn a_line_follows_a_leader_that_gets_away() {
+        let e = entities(3);
+        let moves = [
+            step(e[0], (1, 1), (2, 1)),
+            step(e[1], (2, 1), (3, 1)),
+            step(e[2], (3, 1), (4, 1)),
+        ];
+        let verdicts = resolve(&Map::new(), &standing(&moves), &moves);
+        assert_eq!(verdicts, vec![true, true, true]);
+    }
+
+    #[test]
+    fn nobody_steps_onto_someone_staying_put() {
+        let e = entities(2);
+        let moves = [step(e[0], (1, 1), (2, 1))];
+        let mut standing = standing(&moves);
+        standing.push((e[1], Point::new(2, 1)));
+        let verdicts = resolve(&Map::new(), &standing, &moves);
+        assert_eq!(verdicts, vec![false]);
+    }
+
+    const DOOR: Point = Point { x: 5, y: 5 };
+
+    // Has someone standing next to a `state` door step into it `tries` times
+    // over, returning what became of the door
+    fn bump_door(state: DoorState, player: bool, opens_doors: bool, tries: usize) -> DoorState {
+        let mut ecs = World::default();
+        let mut resources = Resources::default();
+        let mut map = Map::new();
+        map.set_door(DOOR, state);
+        resources.insert(map);
+        resources.insert(Camera::new(DOOR));
+        resources.insert(RandomNumberGenerator::seeded(1));
+        resources.insert(MessageLog::default());
+        resources.insert(GameClock::default());
+
+        let mover = ecs.push((
+            DOOR - Point::new(1, 0),
+            Health { current: 1, max: 1 },
+            Energy::new(NORMAL_SPEED),
+        ));
+        let mut entry = ecs.entry(mover).unwrap();
+        if player {
+            entry.add_component(Player);
+        }
+        if opens_doors {
+            entry.add_component(OpensDoors);
+        }
+
+        let mut schedule = Schedule::builder().add_system(movement_system()).build();
+        for _ in 0..tries {
+            ecs.push((
+                (),
+                WantsToMove {
+                    entity: mover,
+                    destination: DOOR,
+                },
+            ));
+            schedule.execute(&mut ecs, &mut resources);
+        }
+        let map = resources.get::<Map>().unwrap();
+        map.door(DOOR).unwrap()
+    }
+
+    #[test]
+    fn shut_doors_open_for_those_who_can_open_them() {
+        use DoorState::*;
+
+        assert_eq!(bump_door(Closed, true, false, 1), Open);
+        assert_eq!(bump_door(Closed, false, true, 1), Open);
+        assert_eq!(bump_door(Closed, false, false, 10), Closed);
+    }
+
+    #[test]
+    fn locked_doors_yield_only_to_the_player() {
+        use DoorState::*;
+
+        assert_eq!(bump_door(Locked, false, true, 30), Locked);
+        assert_eq!(bump_door(Locked, false, false, 30), Locked);
+        assert_eq!(bump_door(Locked, true, false, 30), Open);
+    }
 }
@@ -7,64 +7,93 @@ use crate::prelude::*;
 #[write_component(Health)]
 #[read_component(Item)]
 #[read_component(Carried)]
+#[read_component(Name)]
+#[read_component(Render)]
+#[read_component(Description)]
+#[read_component(Equippable)]
+#[read_component(StatBonus)]
+#[read_component(Ranged)]
+#[read_component(FieldOfView)]
+#[read_component(Confused)]
+#[write_component(Energy)]
+#[read_component(Travelling)]
 pub fn player_input(
     ecs: &mut SubWorld,
     commands: &mut CommandBuffer,
-    #[resource] key: &Option<VirtualKeyCode>,
+    #[resource] input: &FrameInput,
     #[resource] turn_state: &mut TurnState,
+    #[resource] rng: &mut RandomNumberGenerator,
+    #[resource] map: &Map,
 ) {
     let mut players = <(Entity, &Point)>::query().filter(component::<Player>());
     let mut enemies = <(Entity, &Point)>::query().filter(component::<Enemy>());
 
-    if let Some(key) = *key {
-        let delta = match key {
-            VirtualKeyCode::H | VirtualKeyCode::Left => Point::new(-1, 0),
-            VirtualKeyCode::L | VirtualKeyCode::Right => Point::new(1, 0),
-            VirtualKeyCode::K | VirtualKeyCode::Up => Point::new(0, -1),
-            VirtualKeyCode::J | VirtualKeyCode::Down => Point::new(0, 1),
-            VirtualKeyCode::G => {
-                let (player, player_pos) = players
-                    .iter(ecs)
-                    .find_map(|(entity, pos)| Some((*entity, *pos)))
-                    .unwrap();
-                let mut items = <(Entity, &Item, &Point)>::query();
-                items
-                    .iter(ecs)
-                    .filter(|(_entity, _item, &item_pos)| item_pos == player_pos)
-                    .for_each(|(entity, _item, _item_pos)| {
-                        commands.remove_component::<Point>(*entity);
-                        commands.add_component(*entity, Carried(player));
-                    });
+    if steer_travel(ecs, commands, map, input) {
+        return;
+    }
+
+    if let Some(action) = input.action {
+        // Looking through the log or the pack is free and pauses the game
+        if action == Action::MessageLog {
+            *turn_state = TurnState::MessageHistory;
+            return;
+        }
+        if action == Action::Inventory {
+            *turn_state = TurnState::Inventory;
+            return;
+        }
+
+        let player_ready = <&Energy>::query()
+            .filter(component::<Player>())
+            .iter(ecs)
+            .all(|energy| energy.is_ready());
+        if !player_ready {
+            return;
+        }
+
+        let mut did_something = false;
+        // Energy used up by actions that resolve right here rather than
+        // through a WantsToX message
+        let mut energy_spent = 0;
+
+        let delta = match action {
+            Action::Wait => {
+                did_something = true;
+                energy_spent = ACTION_COST;
+                Point::new(0, 0)
+            }
+            Action::UseSlot(slot) => {
+                if slot > 0 {
+                    did_something = use_item(slot - 1, ecs, commands, turn_state);
+                }
                 Point::new(0, 0)
             }
-            VirtualKeyCode::Key1 => use_item(0, ecs, commands),
-            VirtualKeyCode::Key2 => use_item(1, ecs, commands),
-            VirtualKeyCode::Key3 => use_item(2, ecs, commands),
-            VirtualKeyCode::Key4 => use_item(3, ecs, commands),
-            VirtualKeyCode::Key5 => use_item(4, ecs, commands),
-            VirtualKeyCode::Key6 => use_item(5, ecs, commands),
-            VirtualKeyCode::Key7 => use_item(6, ecs, commands),
-            VirtualKeyCode::Key8 => use_item(7, ecs, commands),
-            VirtualKeyCode::Key9 => use_item(8, ecs, commands),
-            _ => Point::new(0, 0),
+            _ => action.delta().unwrap_or_else(Point::zero),
         };
 
-        let (player_entity, destination) = players
+        let (player_entity, player_pos) = players
             .iter(ecs)
-            .find_map(|(entity, pos)| Some((*entity, *pos + delta)))
+            .map(|(entity, pos)| (*entity, *pos))
+            .next()
             .unwrap();
 
-        if key == VirtualKeyCode::E {
-            if let Ok(mut health) = ecs
-                .entry_mut(player_entity)
-                .unwrap()
-                .get_component_mut::<Health>()
-            {
-                health.current -= 5;
-            }
+        // diagonal steps are only taken when the rules allow them, and never
+        // around a corner; trying one costs nothing
+        if delta.x != 0 && delta.y != 0 && !map.can_step(player_pos, delta) {
+            return;
+        }
+
+        let delta = stumble(ecs, rng, map, delta);
+        let destination = player_pos + delta;
+
+        if action == Action::HurtSelf {
+            hurt_self(ecs, player_entity);
+        }
+
+        if energy_spent > 0 {
+            spend_energy(ecs, player_entity, energy_spent);
         }
 
-        let mut did_something = false;
         if delta.x != 0 || delta.y != 0 {
             let mut hit_something = false;
             enemies
@@ -79,6 +108,7 @@ pub fn player_input(
                         WantsToAttack {
                             attacker: player_entity,
                             victim: *entity,
+                            with: None,
                         },
                     ));
                 });
@@ -93,34 +123,135 @@ pub fn player_input(
                     },
                 ));
             }
-        };
+        }
 
-        //*turn_state = TurnState::PlayerTurn;
+        // In turn-based play, doing anything at all ends the player's turn
+        if did_something && *turn_state == TurnState::AwaitingInput {
+            *turn_state = TurnState::PlayerTurn;
+        }
     }
 }
 
-fn use_item(n: usize, ecs: &mut SubWorld, commands: &mut CommandBuffer) -> Point {
-    let player_entity = <(Entity, &Player)>::query()
+// Any key press while travelling only brings the player to a halt, the
+// explore key sets them off and a click on a tile they have seen sends them
+// there, for the travel system to take from there. Returns whether the input
+// was dealt with.
+fn steer_travel(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    map: &Map,
+    input: &FrameInput,
+) -> bool {
+    let (player, travelling) = <(Entity, Option<&Travelling>)>::query()
+        .filter(component::<Player>())
         .iter(ecs)
-        .find_map(|(entity, _player)| Some(*entity))
+        .map(|(entity, travelling)| (*entity, travelling.is_some()))
+        .next()
         .unwrap();
+    if input.key.is_some() {
+        if travelling {
+            commands.remove_component::<Travelling>(player);
+            return true;
+        }
+        if input.action == Some(Action::Explore) {
+            commands.add_component(player, Travelling::Exploring);
+            return true;
+        }
+        return false;
+    }
+
+    if !input.left_click {
+        return false;
+    }
+    let destination = input.mouse_tile;
+    if let Some(idx) = map.try_idx(destination) {
+        if map.revealed_tiles[idx] && map.can_pass(destination, DoorState::Locked) {
+            commands.add_component(player, Travelling::To(destination));
+        }
+    }
+    true
+}
+
+fn hurt_self(ecs: &mut SubWorld, player: Entity) {
+    if let Ok(health) = ecs.entry_mut(player).unwrap().get_component_mut::<Health>() {
+        health.current -= 5;
+    }
+}
 
-    let item_entity = <(Entity, &Item, &Carried)>::query()
+// A confused player's feet go their own way
+fn stumble(ecs: &SubWorld, rng: &mut RandomNumberGenerator, map: &Map, delta: Point) -> Point {
+    let confused = <&Confused>::query()
+        .filter(component::<Player>())
         .iter(ecs)
-        .filter(|(_, _, carried)| carried.0 == player_entity)
-        .enumerate()
-        .filter(|(item_count, (_, _, _))| *item_count == n)
-        .find_map(|(_, (item_entity, _, _))| Some(*item_entity));
-
-    if let Some(item_entity) = item_entity {
-        commands.push((
-            (),
-            ActivateItem {
-                used_by: player_entity,
-                item: item_entity,
-            },
-        ));
+        .next()
+        .is_some();
+    if !confused || delta == Point::zero() {
+        return delta;
     }
+    *rng.random_slice_entry(map.steps()).unwrap()
+}
 
-    Point::zero()
+// Uses one item off the `n`th stack in the player's inventory
+fn use_item(
+    n: usize,
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    turn_state: &mut TurnState,
+) -> bool {
+    let player_entity = <Entity>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .copied()
+        .next()
+        .unwrap();
+
+    let stacks = carried_stacks(ecs, player_entity);
+
+    match stacks.get(n) {
+        // ranged items need a target picked first, which takes no time
+        Some(stack) if stack.range.is_some() => {
+            let player_pos = <&Point>::query()
+                .filter(component::<Player>())
+                .iter(ecs)
+                .copied()
+                .next()
+                .unwrap();
+            let cursor = visible_enemies(ecs, player_entity)
+                .first()
+                .copied()
+                .unwrap_or(player_pos);
+            commands.add_component(
+                player_entity,
+                Targeting {
+                    item: stack.items[0],
+                    cursor,
+                },
+            );
+            *turn_state = TurnState::Targeting;
+            false
+        }
+        // using a piece of equipment means putting it on
+        Some(stack) if stack.slot.is_some() => {
+            commands.push((
+                (),
+                WantsToEquip {
+                    entity: player_entity,
+                    item: stack.items[0],
+                },
+            ));
+            true
+        }
+        Some(stack) => {
+            commands.push((
+                (),
+                ActivateItem {
+                    used_by: player_entity,
+                    item: stack.items[0],
+                    target: None,
+                },
+            ));
+            true
+        }
+        None => false,
+    }
 }
@@ -5,36 +5,46 @@ use crate::prelude::*;
 #[read_component(MovingRandomly)]
 #[read_component(Health)]
 #[read_component(Player)]
-pub fn random_move(ecs: &SubWorld, commands: &mut CommandBuffer) {
-    let mut movers = <(Entity, &Point, &MovingRandomly)>::query();
+#[read_component(Energy)]
+#[read_component(Confused)]
+#[read_component(Afraid)]
+pub fn random_move(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] rng: &mut RandomNumberGenerator,
+    #[resource] map: &Map,
+) {
+    let mut movers = <(Entity, &Point, &MovingRandomly, &Energy)>::query()
+        .filter(!component::<Confused>() & !component::<Afraid>());
     let mut positions = <(Entity, &Point, &Health)>::query();
-    movers.iter(ecs).for_each(| (entity, pos, _) | {
-        let mut rng = RandomNumberGenerator::new();
-        let destination = match rng.range(0, 4) {
-            0 => Point::new(-1, 0),
-            1 => Point::new(1, 0),
-            2 => Point::new(0, -1),
-            _ => Point::new(0, 1),
-        } + *pos;
+    movers
+        .iter(ecs)
+        .filter(|(_, _, _, energy)| energy.is_ready())
+        .for_each(| (entity, pos, _, _) | {
+            let delta = *rng.random_slice_entry(map.steps()).unwrap();
+            let destination = delta + *pos;
 
-        let mut attacked = false;
-        positions
-            .iter(ecs)
-            .filter(|(_, target_pos, _)| **target_pos == destination)
-            .for_each(|(victim, _, _)| {
-                if ecs.entry_ref(*victim).unwrap().get_component::<Player>().is_ok() {
-                    commands
-                        .push(((), WantsToAttack{
-                            attacker: *entity,
-                            victim: *victim
-                        }));
-                }
-                attacked = true;
-            });
+            let mut attacked = false;
+            positions
+                .iter(ecs)
+                .filter(|(_, target_pos, _)| **target_pos == destination)
+                .for_each(|(victim, _, _)| {
+                    if ecs.entry_ref(*victim).unwrap().get_component::<Player>().is_ok()
+                        && map.can_step(*pos, delta)
+                    {
+                        commands
+                            .push(((), WantsToAttack{
+                                attacker: *entity,
+                                victim: *victim,
+                                with: None,
+                            }));
+                    }
+                    attacked = true;
+                });
 
-        if !attacked {
-            commands
-                .push(((), WantsToMove { entity: *entity, destination }));
-        }
-    });
+            if !attacked {
+                commands
+                    .push(((), WantsToMove { entity: *entity, destination }));
+            }
+        });
 }
\ No newline at end of file
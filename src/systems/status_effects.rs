@@ -0,0 +1,209 @@
+use crate::prelude::*;
+
+// At the start of every turn poison and regeneration do their work, and every
+// status effect ticks down a turn until it wears off
+#[system]
+#[read_component(Point)]
+#[read_component(Player)]
+#[read_component(Confused)]
+#[read_component(Afraid)]
+#[read_component(Poisoned)]
+#[read_component(Slowed)]
+#[read_component(Regenerating)]
+#[write_component(Health)]
+pub fn status_effects(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] log: &mut MessageLog,
+    #[resource] clock: &GameClock,
+) {
+    if !clock.steps.is_multiple_of(GameClock::STEPS_PER_TURN) {
+        return;
+    }
+
+    // poison goes through the effects system, so whatever it kills dies the
+    // same way as from any other damage
+    <(&Point, &Poisoned)>::query()
+        .iter(ecs)
+        .for_each(|(pos, poisoned)| {
+            commands.push((
+                (),
+                Effect {
+                    source: poisoned.by,
+                    kind: EffectKind::Damage(poisoned.damage),
+                    target: *pos,
+                    radius: 0,
+                },
+            ));
+        });
+    <(&mut Health, &Regenerating)>::query()
+        .iter_mut(ecs)
+        .for_each(|(health, regenerating)| {
+            health.current = i32::min(health.max, health.current + regenerating.amount);
+        });
+
+    let player = <Entity>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .copied()
+        .next();
+    let mut expired = Vec::new();
+
+    for (entity, status) in <(Entity, &Confused)>::query().iter(ecs) {
+        match status.turns {
+            turns if turns > 1 => commands.add_component(*entity, Confused { turns: turns - 1 }),
+            _ => {
+                commands.remove_component::<Confused>(*entity);
+                expired.push((*entity, "confused"));
+            }
+        }
+    }
+    for (entity, status) in <(Entity, &Afraid)>::query().iter(ecs) {
+        match status.turns {
+            turns if turns > 1 => commands.add_component(*entity, Afraid { turns: turns - 1 }),
+            _ => {
+                commands.remove_component::<Afraid>(*entity);
+                expired.push((*entity, "afraid"));
+            }
+        }
+    }
+    for (entity, status) in <(Entity, &Poisoned)>::query().iter(ecs) {
+        match status.turns {
+            turns if turns > 1 => commands.add_component(
+                *entity,
+                Poisoned {
+                    turns: turns - 1,
+                    ..*status
+                },
+            ),
+            _ => {
+                commands.remove_component::<Poisoned>(*entity);
+                expired.push((*entity, "poisoned"));
+            }
+        }
+    }
+    for (entity, status) in <(Entity, &Slowed)>::query().iter(ecs) {
+        match status.turns {
+            turns if turns > 1 => commands.add_component(*entity, Slowed { turns: turns - 1 }),
+            _ => {
+                commands.remove_component::<Slowed>(*entity);
+                expired.push((*entity, "slowed"));
+            }
+        }
+    }
+    for (entity, status) in <(Entity, &Regenerating)>::query().iter(ecs) {
+        match status.turns {
+            turns if turns > 1 => commands.add_component(
+                *entity,
+                Regenerating {
+                    turns: turns - 1,
+                    ..*status
+                },
+            ),
+            _ => {
+                commands.remove_component::<Regenerating>(*entity);
+                expired.push((*entity, "regenerating"));
+            }
+        }
+    }
+
+    // only the player's own recoveries are worth a line in the log
+    for (entity, status) in expired {
+        if Some(entity) == player {
+            log.add(
+                clock,
+                Message::new().colored(format!("You are no longer {status}."), GRAY),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs the status effects system `turns` times over, a turn apart
+    fn tick(ecs: &mut World, resources: &mut Resources, turns: u64) {
+        let mut schedule = Schedule::builder()
+            .add_system(status_effects_system())
+            .build();
+        for _ in 0..turns {
+            schedule.execute(ecs, resources);
+            resources.get_mut::<GameClock>().unwrap().steps += GameClock::STEPS_PER_TURN;
+        }
+    }
+
+    fn has<T: legion::storage::Component>(ecs: &World, entity: Entity) -> bool {
+        let entry = ecs.entry_ref(entity).unwrap();
+        entry.get_component::<T>().is_ok()
+    }
+
+    fn setup() -> (World, Resources) {
+        let mut resources = Resources::default();
+        resources.insert(MessageLog::default());
+        resources.insert(GameClock::default());
+        (World::default(), resources)
+    }
+
+    #[test]
+    fn statuses_tick_down_and_wear_off() {
+        let (mut ecs, mut resources) = setup();
+        let rat = ecs.push((
+            Point::zero(),
+            Health { current: 1, max: 1 },
+            Confused { turns: 3 },
+            Slowed { turns: 1 },
+        ));
+
+        tick(&mut ecs, &mut resources, 1);
+        let entry = ecs.entry_ref(rat).unwrap();
+        assert_eq!(entry.get_component::<Confused>().unwrap().turns, 2);
+        assert!(!has::<Slowed>(&ecs, rat));
+
+        tick(&mut ecs, &mut resources, 2);
+        assert!(!has::<Confused>(&ecs, rat));
+    }
+
+    #[test]
+    fn regeneration_heals_up_to_full_while_it_lasts() {
+        let (mut ecs, mut resources) = setup();
+        let player = ecs.push((
+            Player,
+            Point::zero(),
+            Health { current: 1, max: 6 },
+            Regenerating {
+                turns: 4,
+                amount: 2,
+            },
+        ));
+        let health = |ecs: &World| {
+            let entry = ecs.entry_ref(player).unwrap();
+            entry.get_component::<Health>().unwrap().current
+        };
+
+        tick(&mut ecs, &mut resources, 2);
+        assert_eq!(health(&ecs), 5);
+        tick(&mut ecs, &mut resources, 1);
+        assert_eq!(health(&ecs), 6);
+        assert!(has::<Regenerating>(&ecs, player));
+
+        tick(&mut ecs, &mut resources, 1);
+        assert!(!has::<Regenerating>(&ecs, player));
+        let log = resources.get::<MessageLog>().unwrap();
+        assert_eq!(
+            log.entries.last().unwrap().message.segments[0].0,
+            "You are no longer regenerating."
+        );
+    }
+
+    #[test]
+    fn statuses_only_tick_once_a_turn() {
+        let (mut ecs, mut resources) = setup();
+        let rat = ecs.push((Point::zero(), Confused { turns: 2 }));
+
+        resources.get_mut::<GameClock>().unwrap().steps = 1;
+        tick(&mut ecs, &mut resources, 1);
+        let entry = ecs.entry_ref(rat).unwrap();
+        assert_eq!(entry.get_component::<Confused>().unwrap().turns, 2);
+    }
+}
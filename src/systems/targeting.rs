@@ -0,0 +1,146 @@
+use crate::prelude::*;
+
+#[system]
+#[read_component(Point)]
+#[read_component(Player)]
+#[read_component(Enemy)]
+#[read_component(Health)]
+#[read_component(FieldOfView)]
+#[read_component(Ranged)]
+#[read_component(Name)]
+#[read_component(Render)]
+#[read_component(Targeting)]
+#[read_component(Attack)]
+// legion hands over even the smallest resource by reference
+#[allow(clippy::trivially_copy_pass_by_ref)]
+pub fn targeting(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] input: &FrameInput,
+    #[resource] map: &Map,
+    #[resource] mode: &GameMode,
+    #[resource] turn_state: &mut TurnState,
+) {
+    let aiming = <(Entity, &Point, &Targeting)>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .map(|(entity, pos, targeting)| (*entity, *pos, *targeting))
+        .next();
+    match aiming {
+        None => *turn_state = mode.starting_state(),
+        Some((player, _, _)) if input.key == Some(VirtualKeyCode::Escape) => {
+            commands.remove_component::<Targeting>(player);
+            *turn_state = mode.starting_state();
+        }
+        Some((player, player_pos, targeting)) => {
+            let (cursor, fire) = steer_cursor(ecs, map, input, player, targeting.cursor);
+            commands.add_component(
+                player,
+                Targeting {
+                    item: targeting.item,
+                    cursor,
+                },
+            );
+            if !fire {
+                return;
+            }
+
+            // a blocked shot does nothing, the overlay already says what's wrong
+            if let Ok(shot) = aim(ecs, map, player, player_pos, targeting.item, cursor) {
+                match shot {
+                    Some(victim) => commands.push((
+                        (),
+                        WantsToAttack {
+                            attacker: player,
+                            victim,
+                            with: Some(targeting.item),
+                        },
+                    )),
+                    None => commands.push((
+                        (),
+                        ActivateItem {
+                            used_by: player,
+                            item: targeting.item,
+                            target: Some(cursor),
+                        },
+                    )),
+                };
+                commands.remove_component::<Targeting>(player);
+                *turn_state = mode.acting_state();
+            }
+        }
+    }
+}
+
+// Where this frame's input leaves the cursor, and whether it asks to fire
+fn steer_cursor(
+    ecs: &SubWorld,
+    map: &Map,
+    input: &FrameInput,
+    player: Entity,
+    cursor: Point,
+) -> (Point, bool) {
+    let (moved, fire) = match input.action {
+        _ if input.left_click => (input.mouse_tile, true),
+        // cycle through the enemies in sight, closest first
+        Some(Action::NextTarget) => {
+            let enemies = visible_enemies(ecs, player);
+            let next = enemies
+                .iter()
+                .position(|pos| *pos == cursor)
+                .map_or(0, |current| current + 1);
+            let target = enemies.get(next).or_else(|| enemies.first());
+            (target.copied().unwrap_or(cursor), false)
+        }
+        Some(Action::Fire) => (cursor, true),
+        Some(action) => (cursor + action.delta().unwrap_or_else(Point::zero), false),
+        None => (cursor, false),
+    };
+    (if map.in_bounds(moved) { moved } else { cursor }, fire)
+}
+
+// Who a shot at `target` would hit, or why it can't be taken. Items without
+// an attack of their own work on the tile itself, so they hit nobody in
+// particular.
+pub fn aim<W: EntityStore>(
+    ecs: &W,
+    map: &Map,
+    player: Entity,
+    player_pos: Point,
+    item: Entity,
+    target: Point,
+) -> Result<Option<Entity>, &'static str> {
+    let (range, attacks) = match ecs.entry_ref(item) {
+        Ok(entry) => (
+            entry
+                .get_component::<Ranged>()
+                .map_or(0, |ranged| ranged.range),
+            entry.get_component::<Attack>().is_ok(),
+        ),
+        Err(_) => (0, false),
+    };
+    let in_sight = <&FieldOfView>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .any(|fov| fov.visible_tiles.contains(&target));
+
+    if !in_sight {
+        return Err("You can't see that spot.");
+    }
+    if f64::from(DistanceAlg::Pythagoras.distance2d(player_pos, target)) > f64::from(range) {
+        return Err("That is out of range.");
+    }
+    if !map.has_line_of_fire(player_pos, target) {
+        return Err("You don't have a clear shot.");
+    }
+    if !attacks {
+        return Ok(None);
+    }
+
+    <(Entity, &Point)>::query()
+        .filter(component::<Health>())
+        .iter(ecs)
+        .find(|(entity, pos)| **pos == target && **entity != player)
+        .map(|(entity, _)| Some(*entity))
+        .ok_or("There is nothing there to hit.")
+}
@@ -0,0 +1,63 @@
+use super::targeting::aim;
+use crate::prelude::*;
+
+#[system]
+#[read_component(Point)]
+#[read_component(Player)]
+#[read_component(Targeting)]
+#[read_component(Ranged)]
+#[read_component(Name)]
+#[read_component(FieldOfView)]
+#[read_component(Health)]
+#[read_component(Attack)]
+pub fn targeting_render(ecs: &SubWorld, #[resource] camera: &Camera, #[resource] map: &Map) {
+    let aiming = <(Entity, &Point, &Targeting)>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .next();
+    if let Some((player, player_pos, targeting)) = aiming {
+        let name = ecs
+            .entry_ref(targeting.item)
+            .ok()
+            .and_then(|entry| {
+                entry
+                    .get_component::<Name>()
+                    .ok()
+                    .map(|name| name.0.clone())
+            })
+            .unwrap_or_default();
+
+        let cursor = targeting.cursor;
+        let shot = aim(ecs, map, *player, *player_pos, targeting.item, cursor);
+        let color = ColorPair::new(if shot.is_ok() { GREEN } else { RED }, BLACK);
+
+        let mut draw_batch = DrawBatch::new();
+        draw_batch.target(2);
+        let offset = Point::new(camera.left_x, camera.top_y);
+
+        // brackets around the cursor leave whatever stands there in view
+        line2d(LineAlg::Bresenham, *player_pos, cursor)
+            .iter()
+            .filter(|pos| **pos != *player_pos && **pos != cursor)
+            .for_each(|pos| {
+                draw_batch.set(*pos - offset, color, to_cp437('∙'));
+            });
+        draw_batch.set(cursor - offset + Point::new(-1, 0), color, to_cp437('['));
+        draw_batch.set(cursor - offset + Point::new(1, 0), color, to_cp437(']'));
+
+        draw_batch.print_color_centered(
+            3,
+            format!("Aiming the {name}: move with the arrows or mouse, Tab for the next target"),
+            ColorPair::new(GOLD, BLACK),
+        );
+        draw_batch.print_color_centered(
+            4,
+            "F, Enter or click to fire - ESC to cancel",
+            ColorPair::new(GOLD, BLACK),
+        );
+        if let Err(reason) = shot {
+            draw_batch.print_color_centered(5, reason, color);
+        }
+        draw_batch.submit(10200).expect("Targeting Batch Error");
+    }
+}
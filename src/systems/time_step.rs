@@ -1,9 +1,22 @@
-// use crate::prelude::*;
+use crate::prelude::*;
 
-// #[system]
-// pub fn time_step(
-//     ecs: &mut SubWorld,
-//     #[resource] frame_time: &mut f32,
-//     commands: &mut CommandBuffer,
-// ) {
-// }
+// Every time step, each entity gains energy at its own speed, or half of it
+// while slowed. Nobody can bank more than a single action's worth while
+// waiting around.
+#[system]
+#[write_component(Energy)]
+#[read_component(Slowed)]
+pub fn time_step(ecs: &mut SubWorld, #[resource] clock: &mut GameClock) {
+    clock.steps += 1;
+
+    <(&mut Energy, Option<&Slowed>)>::query()
+        .iter_mut(ecs)
+        .for_each(|(energy, slowed)| {
+            let gain = if slowed.is_some() {
+                energy.speed / 2
+            } else {
+                energy.speed
+            };
+            energy.current = i32::min(energy.current + gain, ACTION_COST);
+        });
+}
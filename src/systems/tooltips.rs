@@ -5,16 +5,23 @@ use crate::prelude::*;
 #[read_component(Name)]
 #[read_component(FieldOfView)]
 #[read_component(Player)]
+#[read_component(Health)]
+#[read_component(Confused)]
+#[read_component(Afraid)]
+#[read_component(Poisoned)]
+#[read_component(Slowed)]
+#[read_component(Regenerating)]
 pub fn tooltips(
     ecs: &SubWorld,
-    #[resource] mouse_pos: &Point,
+    #[resource] input: &FrameInput,
     #[resource] camera: &Camera
 ) {
     let mut positions = <(Entity, &Point, &Name)>::query();
     let mut fov = <&FieldOfView>::query().filter(component::<Player>());
 
+    // the camera may have moved since the frame's input came in
     let offset = Point::new(camera.left_x, camera.top_y);
-    let map_pos = *mouse_pos + offset;
+    let map_pos = input.mouse + offset;
 
     let mut draw_batch = DrawBatch::new();
     draw_batch.target(2);
@@ -27,7 +34,7 @@ pub fn tooltips(
             **pos == map_pos && player_fov.visible_tiles.contains(&pos)
         )
         .for_each(|(entity, _, name) | {
-            let screen_pos = *mouse_pos * 2 + Point::new(1, -1);
+            let screen_pos = input.mouse * 2 + Point::new(1, -1);
             let display = if let Ok(health) = ecs.entry_ref(*entity)
                 .unwrap()
                 .get_component::<Health>()
@@ -36,6 +43,15 @@ pub fn tooltips(
             } else {
                 name.0.clone()
             };
+            let statuses: Vec<&str> = statuses(ecs, *entity)
+                .iter()
+                .map(|(status, _, _, _)| *status)
+                .collect();
+            let display = if statuses.is_empty() {
+                display
+            } else {
+                format!("{} ({})", display, statuses.join(", "))
+            };
             draw_batch.print(screen_pos, &display);
         });
     draw_batch.submit(10100).expect("Tooltip Batch Error");
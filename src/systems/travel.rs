@@ -0,0 +1,246 @@
+use crate::prelude::*;
+
+// Takes the player a step at a time towards a clicked tile or the nearest
+// unexplored part of the floor, once player_input has set them off. Any key
+// press brings them to a halt, as does any monster coming into view that
+// wasn't already in sight when they set off.
+#[system]
+#[read_component(Point)]
+#[read_component(Player)]
+#[read_component(Enemy)]
+#[read_component(Name)]
+#[read_component(Render)]
+#[read_component(FieldOfView)]
+#[read_component(Energy)]
+#[read_component(Travelling)]
+pub fn travel(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] map: &Map,
+    #[resource] turn_state: &mut TurnState,
+    #[resource] log: &mut MessageLog,
+    #[resource] clock: &GameClock,
+    #[resource] journey: &mut Journey,
+) {
+    let traveller = <(Entity, &Point, &Travelling)>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .map(|(entity, pos, travelling)| (*entity, *pos, *travelling))
+        .next();
+    let player_ready = <&Energy>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .all(|energy| energy.is_ready());
+    if traveller.is_none() {
+        journey.seen = None;
+    }
+    if let (Some((player, player_pos, travelling)), true) = (traveller, player_ready) {
+        if journey.seen.is_none() {
+            journey.seen = Some(enemies_in_view(ecs, player));
+        }
+        match next_leg(ecs, map, journey, player, player_pos, travelling) {
+            Ok(destination) => {
+                commands.push((
+                    (),
+                    WantsToMove {
+                        entity: player,
+                        destination,
+                    },
+                ));
+                if *turn_state == TurnState::AwaitingInput {
+                    *turn_state = TurnState::PlayerTurn;
+                }
+            }
+            Err(message) => {
+                commands.remove_component::<Travelling>(player);
+                if let Some(message) = message {
+                    log.add(clock, message);
+                }
+            }
+        }
+    }
+}
+
+// Where the player steps next on their way, or why they stop there, if
+// there's anything to tell them
+fn next_leg(
+    ecs: &SubWorld,
+    map: &Map,
+    journey: &mut Journey,
+    player: Entity,
+    player_pos: Point,
+    travelling: Travelling,
+) -> Result<Point, Option<Message>> {
+    let seen = journey.seen.as_deref().unwrap_or_default();
+    if let Some(message) = spotted_enemy(ecs, player, seen) {
+        return Err(Some(message));
+    }
+
+    let targets = match travelling {
+        Travelling::To(destination) if destination == player_pos => return Err(None),
+        Travelling::To(destination) => vec![map.point2d_to_index(destination)],
+        Travelling::Exploring => {
+            let fov = <&FieldOfView>::query()
+                .filter(component::<Player>())
+                .iter(ecs)
+                .next()
+                .unwrap();
+            unexplored_edge(map, fov)
+        }
+    };
+    let destination = match journey.next_step(map, player_pos, targets) {
+        Some(destination) => destination,
+        None if travelling == Travelling::Exploring => {
+            return Err(Some(
+                Message::new().text("There is nothing left to explore here."),
+            ));
+        }
+        None => return Err(Some(Message::new().text("You can't find a way there."))),
+    };
+
+    // the stairs are only ever climbed on purpose
+    if map.tiles[map.point2d_to_index(destination)] == TileType::Stairs
+        && travelling != Travelling::To(destination)
+    {
+        return Err(Some(Message::new().text("You come to the stairs.")));
+    }
+    Ok(destination)
+}
+
+// The monsters the player can see, closest first
+fn enemies_in_view(ecs: &SubWorld, player: Entity) -> Vec<Entity> {
+    let enemies: Vec<(Entity, Point)> = <(Entity, &Point)>::query()
+        .filter(component::<Enemy>())
+        .iter(ecs)
+        .map(|(entity, pos)| (*entity, *pos))
+        .collect();
+    visible_enemies(ecs, player)
+        .iter()
+        .filter_map(|pos| enemies.iter().find(|(_, at)| at == pos))
+        .map(|(entity, _)| *entity)
+        .collect()
+}
+
+// Why the player has to stop, if there's a monster in view other than those
+// they have `seen` already
+fn spotted_enemy(ecs: &SubWorld, player: Entity, seen: &[Entity]) -> Option<Message> {
+    let spotted = enemies_in_view(ecs, player)
+        .into_iter()
+        .find(|enemy| !seen.contains(enemy))?;
+    let (name, color) = display_name(ecs, spotted);
+    Some(
+        Message::new()
+            .text("You stop, seeing ")
+            .colored(name, color)
+            .text("."),
+    )
+}
+
+// The explored tiles that can be walked to and border on something not seen yet
+fn unexplored_edge(map: &Map, fov: &FieldOfView) -> Vec<usize> {
+    let seen = |pos: Point| {
+        map.try_idx(pos)
+            .is_none_or(|idx| map.revealed_tiles[idx] || fov.visible_tiles.contains(&pos))
+    };
+    (0..map.tiles.len())
+        .map(|idx| map.index_to_point2d(idx))
+        .filter(|pos| seen(*pos) && map.can_pass(*pos, DoorState::Locked))
+        .filter(|pos| STEPS.iter().any(|delta| !seen(*pos + *delta)))
+        .map(|pos| map.point2d_to_index(pos))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: Point = Point { x: 5, y: 5 };
+
+    fn setup() -> (World, Resources, Entity) {
+        let mut ecs = World::default();
+        let mut resources = Resources::default();
+        resources.insert(Map::new());
+        resources.insert(TurnState::AwaitingInput);
+        resources.insert(MessageLog::default());
+        resources.insert(GameClock::default());
+        resources.insert(Journey::default());
+
+        let player = ecs.push((
+            Player,
+            START,
+            Travelling::To(START + Point::new(20, 0)),
+            FieldOfView::new(8),
+            Energy {
+                current: ACTION_COST,
+                speed: NORMAL_SPEED,
+            },
+        ));
+        (ecs, resources, player)
+    }
+
+    // A rat at `pos`, in the player's view
+    fn spot_rat(ecs: &mut World, player: Entity, pos: Point) {
+        ecs.push((Enemy, pos, Name("Rat".to_string())));
+        let mut entry = ecs.entry(player).unwrap();
+        let fov = entry.get_component_mut::<FieldOfView>().unwrap();
+        fov.visible_tiles.insert(pos);
+    }
+
+    // Runs the travel system once, returning whether the player is still on
+    // their way
+    fn travel(ecs: &mut World, resources: &mut Resources, player: Entity) -> bool {
+        Schedule::builder()
+            .add_system(travel_system())
+            .build()
+            .execute(ecs, resources);
+        let entry = ecs.entry_ref(player).unwrap();
+        entry.get_component::<Travelling>().is_ok()
+    }
+
+    fn last_message(resources: &Resources) -> String {
+        let log = resources.get::<MessageLog>().unwrap();
+        let entry = log.entries.last().unwrap();
+        entry
+            .message
+            .segments
+            .iter()
+            .map(|(text, _)| text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn monsters_already_in_view_dont_stop_the_player() {
+        let (mut ecs, mut resources, player) = setup();
+        spot_rat(&mut ecs, player, START + Point::new(3, 3));
+
+        for _ in 0..3 {
+            assert!(travel(&mut ecs, &mut resources, player));
+        }
+        assert_eq!(<&WantsToMove>::query().iter(&ecs).count(), 3);
+    }
+
+    #[test]
+    fn a_monster_coming_into_view_stops_the_player() {
+        let (mut ecs, mut resources, player) = setup();
+        spot_rat(&mut ecs, player, START + Point::new(3, 3));
+        assert!(travel(&mut ecs, &mut resources, player));
+
+        spot_rat(&mut ecs, player, START + Point::new(4, -2));
+        assert!(!travel(&mut ecs, &mut resources, player));
+        assert_eq!(last_message(&resources), "You stop, seeing the Rat.");
+    }
+
+    #[test]
+    fn setting_off_again_takes_in_whatever_is_in_view() {
+        let (mut ecs, mut resources, player) = setup();
+        assert!(travel(&mut ecs, &mut resources, player));
+        spot_rat(&mut ecs, player, START + Point::new(3, 3));
+        assert!(!travel(&mut ecs, &mut resources, player));
+
+        // the journey is forgotten once the player has stopped
+        travel(&mut ecs, &mut resources, player);
+        let mut entry = ecs.entry(player).unwrap();
+        entry.add_component(Travelling::To(START + Point::new(20, 0)));
+        assert!(travel(&mut ecs, &mut resources, player));
+    }
+}
@@ -1,49 +1,107 @@
 use crate::prelude::*;
+use legion::world::EntryRef;
 
+// Turns every item used this turn into the Effects it has, leaving the effects
+// system to carry them out
 #[system]
 #[read_component(ActivateItem)]
 #[read_component(ProvidesHealing)]
-#[write_component(Health)]
 #[read_component(ProvidesDungeonMap)]
-pub fn use_items(ecs: &mut SubWorld, commands: &mut CommandBuffer, #[resource] map: &mut Map) {
-    // add our mutable changes to a list to be executed all at once
-    let mut healing_to_apply = Vec::<(Entity, i32)>::new();
-
-    <(Entity, &ActivateItem)>::query()
+#[read_component(InflictsDamage)]
+#[read_component(InflictsConfusion)]
+#[read_component(InflictsFear)]
+#[read_component(InflictsPoison)]
+#[read_component(InflictsSlow)]
+#[read_component(ProvidesRegeneration)]
+#[read_component(ProvidesTeleport)]
+#[read_component(ProvidesBlink)]
+#[read_component(RevealsEntities)]
+#[read_component(AreaOfEffect)]
+#[read_component(Ranged)]
+#[read_component(Charges)]
+#[read_component(Point)]
+#[read_component(Enemy)]
+#[read_component(FieldOfView)]
+#[read_component(Name)]
+#[read_component(Render)]
+#[read_component(Player)]
+#[write_component(Energy)]
+#[write_component(ItemsUsed)]
+pub fn use_items(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] log: &mut MessageLog,
+    #[resource] clock: &GameClock,
+) {
+    let activations: Vec<(Entity, ActivateItem)> = <(Entity, &ActivateItem)>::query()
         .iter(ecs)
-        .for_each(|(entity, activate)| {
-            // entry_ref allows accessing an entity that's not returned in a query,
-            // returns a reference to a single entity
-            let item = ecs.entry_ref(activate.item);
-            let mut rng = RandomNumberGenerator::new();
-            if let Ok(item) = item {
-                // if let Ok => lets us only run code if item/etc exists
-                //  get_component allows access to components assigned to the entity, but might be None
-                if let Ok(healing) = item.get_component::<ProvidesHealing>() {
-                    // add healing to our list of healing events to process
-                    healing_to_apply.push((activate.used_by, rng.range(2, healing.amount)));
-                }
+        .map(|(entity, activate)| (*entity, *activate))
+        .collect();
 
-                if let Ok(_mapper) = item.get_component::<ProvidesDungeonMap>() {
-                    // go through map tiles and make them all revealed
-                    map.revealed_tiles.iter_mut().for_each(|t| {
-                        if rng.roll_dice(1, 6) > 4 {
-                            *t = true;
-                        }
-                    });
-                }
-            }
+    for (message, activate) in activations {
+        let (user, user_color) = display_name(ecs, activate.used_by);
+        let (item_name, item_color) = display_name(ecs, activate.item);
+        let verb = if is_player(ecs, activate.used_by) {
+            " use "
+        } else {
+            " uses "
+        };
+        log.add(
+            clock,
+            Message::new()
+                .colored(capitalize(&user), user_color)
+                .text(verb)
+                .colored(item_name, item_color)
+                .text("."),
+        );
 
-            commands.remove(activate.item);
-            commands.remove(*entity);
-        });
+        if let Ok(item) = ecs.entry_ref(activate.item) {
+            let target = activate
+                .target
+                .unwrap_or_else(|| default_target(ecs, activate.used_by, &item));
+            let radius = item
+                .get_component::<AreaOfEffect>()
+                .map_or(0, |area| area.radius);
 
-    for heal in healing_to_apply.iter() {
-        if let Ok(mut target) = ecs.entry_mut(heal.0) {
-            if let Ok(health) = target.get_component_mut::<Health>() {
-                // to prevent overhealing, clamp to max
-                health.current = i32::min(health.max, health.current + heal.1);
+            for kind in effects_of(&item) {
+                commands.push((
+                    (),
+                    Effect {
+                        source: activate.used_by,
+                        kind,
+                        target,
+                        radius,
+                    },
+                ));
+            }
+
+            match item.get_component::<Charges>() {
+                Ok(Charges(left)) if *left > 1 => {
+                    commands.add_component(activate.item, Charges(left - 1));
+                }
+                _ => commands.remove(activate.item),
             }
         }
+
+        record_use(ecs, activate.used_by, activate.item);
+        spend_energy(ecs, activate.used_by, USE_ITEM_COST);
+        commands.remove(message);
+    }
+}
+
+// Items that need aiming go for the closest enemy in sight when nobody picked a
+// tile for them, everything else is used on its user
+fn default_target(ecs: &SubWorld, user: Entity, item: &EntryRef) -> Point {
+    let own_pos = ecs
+        .entry_ref(user)
+        .ok()
+        .and_then(|entry| entry.get_component::<Point>().ok().copied())
+        .unwrap_or_else(Point::zero);
+    if item.get_component::<Ranged>().is_err() {
+        return own_pos;
     }
+    visible_enemies(ecs, user)
+        .first()
+        .copied()
+        .unwrap_or(own_pos)
 }
@@ -1,6 +1,68 @@
-#[derive(Copy, Clone, Debug, PartialEq)]
+use crate::prelude::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum TurnState {
+    MainMenu,
+    SeedEntry,
+    PracticeMenu,
+    Options,
+    Paused,
     GamePlay,
+    AwaitingInput,
+    PlayerTurn,
+    MonsterTurn,
+    NextFloor,
+    MessageHistory,
+    Inventory,
+    Targeting,
+    LevelUp,
     GameOver,
     Victory,
 }
+
+// How a run advances: monsters either act on their own clock (RealTime)
+// or only once the player has acted (TurnBased).
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GameMode {
+    RealTime,
+    TurnBased,
+}
+
+impl GameMode {
+    pub fn starting_state(self) -> TurnState {
+        match self {
+            GameMode::RealTime => TurnState::GamePlay,
+            GameMode::TurnBased => TurnState::AwaitingInput,
+        }
+    }
+
+    // The state that carries out an action the player has just picked
+    pub fn acting_state(self) -> TurnState {
+        match self {
+            GameMode::RealTime => TurnState::GamePlay,
+            GameMode::TurnBased => TurnState::PlayerTurn,
+        }
+    }
+}
+
+// Options that change how a run plays out, fixed when it starts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Rules {
+    // eight-way movement for the player and monsters alike
+    pub diagonals: bool,
+}
+
+// Counts time steps since the start of the run. A normal speed entity
+// gets to act once per turn.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GameClock {
+    pub steps: u64,
+}
+
+impl GameClock {
+    pub const STEPS_PER_TURN: u64 = 10;
+
+    pub fn turn(&self) -> u64 {
+        self.steps / Self::STEPS_PER_TURN
+    }
+}